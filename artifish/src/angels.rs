@@ -5,7 +5,7 @@ use rand_chacha::ChaCha20Rng;
 use super::lang::*;
 use crate::{
     color::Color,
-    fish::{Action, Fish},
+    fish::{Action, Fish, FishIds},
     vec2::Vec2,
 };
 
@@ -92,25 +92,58 @@ pub fn ass_is_grass() -> Program {
     }
 }
 
-fn make_angel(rng: &mut ChaCha20Rng, program: Program, color: Color, tag: &str) -> Fish {
+fn make_angel(
+    rng: &mut ChaCha20Rng,
+    ids: &mut FishIds,
+    program: Program,
+    color: Color,
+    tag: &str,
+) -> Fish {
+    let id = ids.take();
     Fish {
+        id,
+        founder: id,
         x: rng.gen_range(0.0..crate::MAX_X),
         y: rng.gen_range(0.0..crate::MAX_Y),
         energy: NotNan::from_inner(500.0),
+        peak_energy: NotNan::from_inner(500.0),
         velocity: Vec2::zero(),
-        program: program,
-        color: color,
+        program_hash: program.shape_hash(),
+        program,
+        color,
         is_man_made: true,
         tag: Some(tag.to_owned()),
+        age: 0,
+        last_action: Action::Pass,
     }
 }
 
-pub fn generate_angel(mut rng: &mut ChaCha20Rng) -> Fish {
-    branch_using!(rng, {
-        make_angel(rng, smartie(), Color::RED, "SMRT"),
-        make_angel(rng, toast_niet_kannibaal(), Color::BLUE, "TNK"),
-        make_angel(rng, ass_is_grass(), Color::GREEN, "ASS"),
-    })
+/// The hand written programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AngelKind {
+    Smartie,
+    ToastNietKannibaal,
+    AssIsGrass,
+}
+
+/// An angel of the given kind at a random spot.
+pub fn make_angel_of_kind(rng: &mut ChaCha20Rng, ids: &mut FishIds, kind: AngelKind) -> Fish {
+    match kind {
+        AngelKind::Smartie => make_angel(rng, ids, smartie(), Color::RED, "SMRT"),
+        AngelKind::ToastNietKannibaal => {
+            make_angel(rng, ids, toast_niet_kannibaal(), Color::BLUE, "TNK")
+        }
+        AngelKind::AssIsGrass => make_angel(rng, ids, ass_is_grass(), Color::GREEN, "ASS"),
+    }
+}
+
+pub fn generate_angel(mut rng: &mut ChaCha20Rng, ids: &mut FishIds) -> Fish {
+    let kind = branch_using!(rng, {
+        AngelKind::Smartie,
+        AngelKind::ToastNietKannibaal,
+        AngelKind::AssIsGrass,
+    });
+    make_angel_of_kind(rng, ids, kind)
 }
 
 // weten waar de rand is
@@ -0,0 +1,94 @@
+// Which part of the aquarium ends up on screen.
+
+use graphics::math::Matrix2d;
+use graphics::Transformed;
+
+use crate::fish::Fish;
+use crate::vec2::Vec2;
+use crate::{MAX_X, MAX_Y, MAX_ZOOM, MIN_ZOOM};
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    /// World position shown in the middle of the window.
+    pub center: Vec2,
+    /// Screen pixels per world unit.
+    pub zoom: f64,
+    /// Window size in screen pixels.
+    pub viewport: [f64; 2],
+    /// Id of the fish to keep in the middle of the window.
+    pub following: Option<u64>,
+}
+
+impl Camera {
+    /// A camera showing the whole aquarium.
+    pub fn new(viewport: [f64; 2]) -> Self {
+        let mut camera = Camera {
+            center: Vec2::zero(),
+            zoom: 1.0,
+            viewport,
+            following: None,
+        };
+        camera.reset();
+        camera
+    }
+
+    pub fn reset(&mut self) {
+        self.center = Vec2::new(MAX_X / 2.0, MAX_Y / 2.0);
+        self.zoom = (self.viewport[0] / MAX_X).min(self.viewport[1] / MAX_Y);
+        self.following = None;
+    }
+
+    /// Turn a transform for screen coordinates into one for world coordinates.
+    pub fn transform(&self, screen: Matrix2d) -> Matrix2d {
+        screen
+            .trans(self.viewport[0] / 2.0, self.viewport[1] / 2.0)
+            .zoom(self.zoom)
+            .trans(-self.center.x, -self.center.y)
+    }
+
+    pub fn screen_to_world(&self, screen: [f64; 2]) -> Vec2 {
+        Vec2::new(
+            (screen[0] - self.viewport[0] / 2.0) / self.zoom + self.center.x,
+            (screen[1] - self.viewport[1] / 2.0) / self.zoom + self.center.y,
+        )
+    }
+
+    pub fn world_to_screen(&self, x: f64, y: f64) -> [f64; 2] {
+        [
+            (x - self.center.x) * self.zoom + self.viewport[0] / 2.0,
+            (y - self.center.y) * self.zoom + self.viewport[1] / 2.0,
+        ]
+    }
+
+    /// Move the view by a distance in screen pixels. Stops following.
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.center -= Vec2::new(dx, dy) / self.zoom;
+        self.following = None;
+    }
+
+    /// Zoom in (factor > 1) or out, keeping the world point under the given
+    /// screen position in place.
+    pub fn zoom_at(&mut self, factor: f64, screen: [f64; 2]) {
+        let anchor = self.screen_to_world(screen);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let moved = self.screen_to_world(screen);
+        self.center += anchor - moved;
+    }
+
+    /// Keep the followed fish in view, or stop following once it's gone.
+    pub fn track(&mut self, fishes: &[Fish]) {
+        if let Some(id) = self.following {
+            match fishes.iter().find(|fish| fish.id == id) {
+                Some(fish) => self.center = Vec2::new(fish.x, fish.y),
+                None => self.following = None,
+            }
+        }
+    }
+
+    /// Whether a circle in world coordinates is at least partly on screen.
+    pub fn is_visible(&self, x: f64, y: f64, radius: f64) -> bool {
+        let half_width = self.viewport[0] / 2.0 / self.zoom + radius;
+        let half_height = self.viewport[1] / 2.0 / self.zoom + radius;
+        (x - self.center.x).abs() <= half_width && (y - self.center.y).abs() <= half_height
+    }
+}
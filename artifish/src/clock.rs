@@ -0,0 +1,89 @@
+// The fixed time step shared by the window and the terminal front-ends.
+
+use std::time::Instant;
+
+use crate::{MAX_TICKS_PER_UPDATE, N_TICKS};
+
+/// Simulated seconds per tick.
+pub const TIME_STEP: f64 = 1.0 / N_TICKS as f64;
+
+pub struct Clock {
+    // Simulated time not yet spent on ticks
+    elapsed_time: f64,
+    // Ticks per second of wall time, counted since `tps_since`
+    tps: f64,
+    tps_ticks: u32,
+    tps_since: Instant,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock {
+            elapsed_time: 0.0,
+            tps: 0.0,
+            tps_ticks: 0,
+            tps_since: Instant::now(),
+        }
+    }
+
+    /// Add `dt` seconds of wall time at `speed` and return how many ticks
+    /// fit in the simulated time, keeping the remainder for the next call.
+    /// At most `MAX_TICKS_PER_UPDATE`, dropping the rest so a slow machine
+    /// doesn't fall further and further behind.
+    pub fn ticks_due(&mut self, dt: f64, speed: f64) -> u32 {
+        self.elapsed_time += dt * speed;
+        let mut num_ticks = 0;
+        while self.elapsed_time >= TIME_STEP {
+            if num_ticks == MAX_TICKS_PER_UPDATE {
+                self.elapsed_time = 0.0;
+                break;
+            }
+            self.elapsed_time -= TIME_STEP;
+            num_ticks += 1;
+        }
+        num_ticks
+    }
+
+    /// Forget the simulated time not yet spent, e.g. while paused.
+    pub fn reset(&mut self) {
+        self.elapsed_time = 0.0;
+    }
+
+    pub fn count_tick(&mut self) {
+        self.tps_ticks += 1;
+    }
+
+    /// Recompute the ticks per second about once a second.
+    pub fn update_tps(&mut self) {
+        let since = self.tps_since.elapsed().as_secs_f64();
+        if since >= 1.0 {
+            self.tps = self.tps_ticks as f64 / since;
+            self.tps_ticks = 0;
+            self.tps_since = Instant::now();
+        }
+    }
+
+    pub fn tps(&self) -> f64 {
+        self.tps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_remainder_and_caps_ticks() {
+        let mut clock = Clock::new();
+        assert_eq!(clock.ticks_due(TIME_STEP * 0.75, 1.0), 0);
+        assert_eq!(clock.ticks_due(TIME_STEP * 0.75, 1.0), 1);
+        assert_eq!(clock.ticks_due(TIME_STEP * 1.5, 2.0), 3);
+
+        clock.reset();
+        assert_eq!(clock.ticks_due(TIME_STEP * 0.75, 1.0), 0);
+
+        let behind = (MAX_TICKS_PER_UPDATE + 10) as f64 * TIME_STEP;
+        assert_eq!(clock.ticks_due(behind, 1.0), MAX_TICKS_PER_UPDATE);
+        assert_eq!(clock.ticks_due(TIME_STEP * 0.5, 1.0), 0);
+    }
+}
@@ -1,6 +1,9 @@
 use rand::Rng;
 use rand_chacha::ChaCha20Rng;
 
+// Channels never get darker than this through mutation, so fishes stay visible
+pub const MIN_CHANNEL: f32 = 0.1;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Color {
     pub inner: [f32; 4],
@@ -25,10 +28,10 @@ impl Color {
     pub fn mutate(&self, rng: &mut ChaCha20Rng) -> Color {
         let mut color = self.inner;
 
-        for i in 0..3 {
+        for channel in color.iter_mut().take(3) {
             let delta = 0.1;
-            color[i] += rng.gen_range(-delta..delta);
-            color[i] = color[i].clamp(0.1, 1.0);
+            *channel += rng.gen_range(-delta..delta);
+            *channel = channel.clamp(MIN_CHANNEL, 1.0);
         }
 
         Color { inner: color }
@@ -45,6 +48,31 @@ impl Color {
         }
     }
 
+    /// A fully saturated colour, hue going from 0 to 1 around the colour wheel.
+    pub fn from_hue(hue: f64) -> Color {
+        let h = hue.rem_euclid(1.0) as f32 * 6.0;
+        let x = 1.0 - (h % 2.0 - 1.0).abs();
+        let [r, g, b] = match h as u32 {
+            0 => [1.0, x, 0.0],
+            1 => [x, 1.0, 0.0],
+            2 => [0.0, 1.0, x],
+            3 => [0.0, x, 1.0],
+            4 => [x, 0.0, 1.0],
+            _ => [1.0, 0.0, x],
+        };
+        Color::new([r, g, b, 1.0])
+    }
+
+    /// Blend towards `other`, by `t` from 0 to 1.
+    pub fn lerp(&self, other: Color, t: f64) -> Color {
+        let t = t.clamp(0.0, 1.0) as f32;
+        let mut color = self.inner;
+        for (channel, target) in color.iter_mut().zip(other.inner.iter()) {
+            *channel += (target - *channel) * t;
+        }
+        Color { inner: color }
+    }
+
     pub const GREEN: Color = Color::new([0.0, 1.0, 0.0, 1.0]);
     pub const RED: Color = Color::new([1.0, 0.0, 0.0, 1.0]);
     pub const BLUE: Color = Color::new([0.0, 0.0, 1.0, 1.0]);
@@ -0,0 +1,138 @@
+// Ways to colour fishes on screen, to see at a glance which fishes are
+// related, rich, old or busy.
+
+use crate::color::Color;
+use crate::fish::{Action, Fish};
+use crate::{HEATMAP_MAX_AGE, HEATMAP_MAX_ENERGY, STARVATION_ENERGY};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// The colour each fish inherited from its parent.
+    Inherited,
+    /// One colour per founder, shared by all its descendants.
+    Lineage,
+    /// One colour per program shape, ignoring constants.
+    Program,
+    Energy,
+    Age,
+    LastAction,
+    /// Man made fishes against randomly generated ones.
+    Origin,
+}
+
+const COLD: Color = Color::new([0.1, 0.2, 1.0, 1.0]);
+const HOT: Color = Color::new([1.0, 0.2, 0.1, 1.0]);
+const PASS_COLOR: Color = Color::new([0.5, 0.5, 0.5, 1.0]);
+const MOVE_COLOR: Color = Color::new([0.2, 0.9, 0.2, 1.0]);
+const SET_VELOCITY_COLOR: Color = Color::new([0.2, 0.6, 1.0, 1.0]);
+const SPLIT_COLOR: Color = Color::new([1.0, 0.9, 0.2, 1.0]);
+const BITE_COLOR: Color = Color::new([1.0, 0.2, 0.2, 1.0]);
+const MAN_MADE_COLOR: Color = Color::new([1.0, 0.6, 0.1, 1.0]);
+const RANDOM_COLOR: Color = Color::new([0.6, 0.6, 0.8, 1.0]);
+
+/// Spread consecutive numbers far apart on the colour wheel.
+fn hue_of(n: u64) -> f64 {
+    (n as f64 * 0.618_033_988_75).fract()
+}
+
+/// A hue for a hash. Hashes are too big for `hue_of`, whose fractions are
+/// lost to rounding, so this takes their top 53 bits as a fraction instead.
+fn hue_of_hash(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Where `value` falls between `low` and `high` on a log scale, from 0 to 1.
+fn log_scale(value: f64, low: f64, high: f64) -> f64 {
+    ((value.max(low) / low).ln() / (high / low).ln()).clamp(0.0, 1.0)
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 7] = [
+        ColorMode::Inherited,
+        ColorMode::Lineage,
+        ColorMode::Program,
+        ColorMode::Energy,
+        ColorMode::Age,
+        ColorMode::LastAction,
+        ColorMode::Origin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Inherited => "inherited",
+            ColorMode::Lineage => "lineage",
+            ColorMode::Program => "program",
+            ColorMode::Energy => "energy",
+            ColorMode::Age => "age",
+            ColorMode::LastAction => "last action",
+            ColorMode::Origin => "origin",
+        }
+    }
+
+    pub fn next(&self) -> ColorMode {
+        let i = ColorMode::ALL.iter().position(|mode| mode == self).unwrap();
+        ColorMode::ALL[(i + 1) % ColorMode::ALL.len()]
+    }
+
+    pub fn fish_color(&self, fish: &Fish) -> Color {
+        match self {
+            ColorMode::Inherited => fish.color,
+            ColorMode::Lineage => Color::from_hue(hue_of(fish.founder)),
+            ColorMode::Program => Color::from_hue(hue_of_hash(fish.program_hash)),
+            ColorMode::Energy => COLD.lerp(
+                HOT,
+                log_scale(
+                    fish.energy.into_inner(),
+                    STARVATION_ENERGY,
+                    HEATMAP_MAX_ENERGY,
+                ),
+            ),
+            ColorMode::Age => COLD.lerp(HOT, fish.age as f64 / HEATMAP_MAX_AGE as f64),
+            ColorMode::LastAction => match fish.last_action {
+                Action::Pass => PASS_COLOR,
+                Action::Move(_) => MOVE_COLOR,
+                Action::SetVelocity(..) => SET_VELOCITY_COLOR,
+                Action::Split(..) => SPLIT_COLOR,
+                Action::Bite(..) => BITE_COLOR,
+            },
+            ColorMode::Origin if fish.is_man_made => MAN_MADE_COLOR,
+            ColorMode::Origin => RANDOM_COLOR,
+        }
+    }
+
+    /// Colour swatches and what they mean. Modes that hand out arbitrary
+    /// colours only get an explanation.
+    pub fn legend(&self) -> Vec<(Option<Color>, String)> {
+        let scale = |low: String, high: String| {
+            vec![
+                (Some(COLD), low),
+                (Some(COLD.lerp(HOT, 0.5)), String::new()),
+                (Some(HOT), high),
+            ]
+        };
+        match self {
+            ColorMode::Inherited => vec![(None, "mutates a little with every split".to_owned())],
+            ColorMode::Lineage => vec![(None, "one colour per founder".to_owned())],
+            ColorMode::Program => vec![(None, "one colour per program shape".to_owned())],
+            ColorMode::Energy => scale(
+                format!("{} energy", STARVATION_ENERGY),
+                format!("{} energy or more", HEATMAP_MAX_ENERGY),
+            ),
+            ColorMode::Age => scale(
+                "newborn".to_owned(),
+                format!("{} ticks or older", HEATMAP_MAX_AGE),
+            ),
+            ColorMode::LastAction => vec![
+                (Some(PASS_COLOR), "Pass".to_owned()),
+                (Some(MOVE_COLOR), "Move".to_owned()),
+                (Some(SET_VELOCITY_COLOR), "SetVelocity".to_owned()),
+                (Some(SPLIT_COLOR), "Split".to_owned()),
+                (Some(BITE_COLOR), "Bite".to_owned()),
+            ],
+            ColorMode::Origin => vec![
+                (Some(MAN_MADE_COLOR), "man made".to_owned()),
+                (Some(RANDOM_COLOR), "random".to_owned()),
+            ],
+        }
+    }
+}
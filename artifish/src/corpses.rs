@@ -0,0 +1,146 @@
+// What's left of starved fishes. Corpses sink and drift with the current,
+// slowly rot away, and can be eaten by any fish that covers them.
+
+use decorum::NotNan;
+
+use crate::color::Color;
+use crate::environment::Environment;
+use crate::fish::{Energy, Fish};
+use crate::food::Food;
+use crate::ledger::EnergyLedger;
+use crate::physics;
+use crate::vec2::Vec2;
+use crate::{BOUNDARY_MODE, MOVE_SPEED};
+use crate::{
+    CORPSE_BODY_FRACTION, CORPSE_DECAY_TO_FOOD, CORPSE_HALF_LIFE, CORPSE_SINK_SPEED,
+    FOOD_PARTICLE_ENERGY,
+};
+
+// Corpses smaller than this are gone
+const MIN_CORPSE_ENERGY: f64 = 1.0;
+
+#[derive(Clone, Debug)]
+pub struct Corpse {
+    pub x: f64,
+    pub y: f64,
+    pub energy: Energy,
+    pub color: Color,
+    /// Rotted away energy not yet turned into food.
+    pub decayed: f64,
+}
+
+impl Corpse {
+    /// What the fish leaves behind: its last energy plus its body, which is
+    /// `CORPSE_BODY_FRACTION` of the largest it has been.
+    pub fn from_fish(fish: &Fish) -> Self {
+        Corpse {
+            x: fish.x,
+            y: fish.y,
+            energy: fish.energy + fish.peak_energy * CORPSE_BODY_FRACTION,
+            color: fish.color.darken(0.3),
+            decayed: 0.0,
+        }
+    }
+
+    /// Same size as a fish with the same energy.
+    pub fn radius(&self) -> f64 {
+        (self.energy / std::f64::consts::PI).into_inner().cbrt()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Corpses {
+    pub corpses: Vec<Corpse>,
+}
+
+impl Corpses {
+    pub fn new() -> Self {
+        Corpses::default()
+    }
+
+    pub fn add(&mut self, corpse: Corpse) {
+        self.corpses.push(corpse);
+    }
+
+    /// Drift, sink and rot. Corpses meet the walls like fishes do, those
+    /// absorbed by a wall leak away. Rotted energy either turns into food
+    /// particles where the corpse is, or is lost.
+    pub fn update(
+        &mut self,
+        environment: &Environment,
+        food: &mut Food,
+        ledger: &mut EnergyLedger,
+        delta_time: f64,
+    ) {
+        let keep_fraction = 0.5f64.powf(delta_time / CORPSE_HALF_LIFE);
+
+        for corpse in self.corpses.iter_mut() {
+            let mut drift =
+                environment.current(corpse.x, corpse.y) + Vec2::new(0.0, CORPSE_SINK_SPEED);
+            corpse.x += drift.x * delta_time * MOVE_SPEED;
+            corpse.y += drift.y * delta_time * MOVE_SPEED;
+            physics::apply_boundary_to_point(
+                &mut corpse.x,
+                &mut corpse.y,
+                &mut drift,
+                BOUNDARY_MODE,
+            );
+
+            let mut rotted = corpse.energy.into_inner() * (1.0 - keep_fraction);
+            if corpse.energy.into_inner() - rotted < MIN_CORPSE_ENERGY {
+                rotted = corpse.energy.into_inner();
+            }
+            corpse.energy -= rotted;
+
+            if CORPSE_DECAY_TO_FOOD {
+                corpse.decayed += rotted;
+                // Release the rest along with the last of the corpse
+                let is_gone = corpse.energy <= 0.0;
+                while corpse.decayed >= FOOD_PARTICLE_ENERGY || (is_gone && corpse.decayed > 0.0) {
+                    let energy = corpse.decayed.min(FOOD_PARTICLE_ENERGY);
+                    food.add(corpse.x, corpse.y, NotNan::from_inner(energy));
+                    corpse.decayed -= energy;
+                    ledger.decomposed += energy;
+                }
+            } else {
+                ledger.decay += rotted;
+            }
+        }
+
+        self.corpses.retain(|corpse| {
+            let in_bounds = physics::is_point_in_bounds(corpse.x, corpse.y);
+            if !in_bounds {
+                ledger.leaked += corpse.energy.into_inner() + corpse.decayed;
+            }
+            in_bounds && corpse.energy > 0.0
+        });
+    }
+
+    /// Let fishes eat the corpses they cover, like food. Returns the energy
+    /// eaten.
+    pub fn feed(&mut self, fishes: &mut [Fish]) -> f64 {
+        let mut eaten = 0.0;
+        for fish in fishes.iter_mut() {
+            let radius = fish.radius();
+            self.corpses.retain(|corpse| {
+                if fish.displacement_to_point(corpse.x, corpse.y).length() < radius {
+                    // Leftover rot goes down with the rest
+                    let energy = corpse.energy.into_inner() + corpse.decayed;
+                    fish.energy += energy;
+                    eaten += energy;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        eaten
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        self.corpses
+            .iter()
+            .map(|corpse| corpse.energy.into_inner() + corpse.decayed)
+            .sum()
+    }
+}
@@ -0,0 +1,268 @@
+// The water the fishes swim in. Light, temperature and nutrients vary over
+// the aquarium and, optionally, over time.
+
+use crate::vec2::Vec2;
+use crate::{COLD_MOVE_COST, DAY_LENGTH, NIGHT_LIGHT, POOR_SPLIT_COST, VORTEX_SPEED};
+use crate::{MAX_X, MAX_Y, SEASON_LENGTH, WINTER_TEMPERATURE};
+
+/// Position in coordinates where the aquarium is the unit square.
+fn unit_coordinates(x: f64, y: f64) -> (f64, f64) {
+    ((x / MAX_X).clamp(0.0, 1.0), (y / MAX_Y).clamp(0.0, 1.0))
+}
+
+/// Indices and weights of the four grid points around a position, for
+/// bilinear interpolation over a grid spanning the aquarium.
+fn grid_weights(columns: usize, rows: usize, x: f64, y: f64) -> [(usize, f64); 4] {
+    let (u, v) = unit_coordinates(x, y);
+    let gx = u * (columns - 1) as f64;
+    let gy = v * (rows - 1) as f64;
+    let (c0, r0) = (gx.floor() as usize, gy.floor() as usize);
+    let (c1, r1) = ((c0 + 1).min(columns - 1), (r0 + 1).min(rows - 1));
+    let (fx, fy) = (gx - c0 as f64, gy - r0 as f64);
+    [
+        (r0 * columns + c0, (1.0 - fx) * (1.0 - fy)),
+        (r0 * columns + c1, fx * (1.0 - fy)),
+        (r1 * columns + c0, (1.0 - fx) * fy),
+        (r1 * columns + c1, fx * fy),
+    ]
+}
+
+/// A value between 0 and 1 for every point of the aquarium.
+#[derive(Clone, Debug)]
+pub enum Map {
+    Constant(f64),
+    /// Goes from `start` to `end` along `direction`, in coordinates where the
+    /// aquarium is the unit square.
+    Gradient {
+        direction: Vec2,
+        start: f64,
+        end: f64,
+    },
+    /// Values at evenly spaced points spanning the aquarium, row by row,
+    /// interpolated bilinearly in between.
+    Grid {
+        columns: usize,
+        rows: usize,
+        values: Vec<f64>,
+    },
+}
+
+impl Map {
+    pub fn sample(&self, x: f64, y: f64) -> f64 {
+        let (u, v) = unit_coordinates(x, y);
+        match self {
+            Map::Constant(value) => *value,
+            Map::Gradient {
+                direction,
+                start,
+                end,
+            } => {
+                let t = (u * direction.x + v * direction.y).clamp(0.0, 1.0);
+                start + (end - start) * t
+            }
+            Map::Grid {
+                columns,
+                rows,
+                values,
+            } => grid_weights(*columns, *rows, x, y)
+                .iter()
+                .map(|&(ix, weight)| values[ix] * weight)
+                .sum(),
+        }
+    }
+}
+
+/// A flow of water, in velocity units.
+#[derive(Clone, Debug)]
+pub enum Current {
+    Uniform(Vec2),
+    /// A Rankine vortex: turning like a solid body inside the core, and
+    /// slowing down with distance outside it. Positive speeds turn clockwise
+    /// on screen.
+    Vortex {
+        center: Vec2,
+        speed: f64,
+        core_radius: f64,
+    },
+    /// Flow between two walls of the aquarium, fastest halfway and still at
+    /// the walls. The walls are the ones parallel to `velocity`.
+    Laminar {
+        velocity: Vec2,
+    },
+    /// Flow velocities at evenly spaced points, like `Map::Grid`.
+    Grid {
+        columns: usize,
+        rows: usize,
+        velocities: Vec<Vec2>,
+    },
+}
+
+impl Current {
+    pub fn sample(&self, x: f64, y: f64) -> Vec2 {
+        match self {
+            Current::Uniform(velocity) => *velocity,
+            Current::Vortex {
+                center,
+                speed,
+                core_radius,
+            } => {
+                let offset = Vec2::new(x, y) - *center;
+                let distance = offset.length();
+                if distance == 0.0 {
+                    return Vec2::zero();
+                }
+                let tangent = Vec2::new(-offset.y, offset.x) / distance;
+                let falloff = if distance < *core_radius {
+                    distance / core_radius
+                } else {
+                    core_radius / distance
+                };
+                tangent * (speed * falloff)
+            }
+            Current::Laminar { velocity } => {
+                let (u, v) = unit_coordinates(x, y);
+                let across = Vec2::new(-velocity.y, velocity.x).normalized();
+                let s = (u * across.x + v * across.y).abs().clamp(0.0, 1.0);
+                *velocity * (4.0 * s * (1.0 - s))
+            }
+            Current::Grid {
+                columns,
+                rows,
+                velocities,
+            } => grid_weights(*columns, *rows, x, y)
+                .iter()
+                .fold(Vec2::zero(), |sum, &(ix, weight)| {
+                    sum + velocities[ix] * weight
+                }),
+        }
+    }
+}
+
+/// Periodic dimming of a map, e.g. night or winter. At the trough the map is
+/// scaled down to `low`, at the peak it is left as is.
+#[derive(Clone, Debug)]
+pub struct Cycle {
+    pub period: f64,
+    pub low: f64,
+}
+
+impl Cycle {
+    /// None if the period is not positive, so a zero length turns it off.
+    pub fn new(period: f64, low: f64) -> Option<Self> {
+        if period > 0.0 {
+            Some(Cycle { period, low })
+        } else {
+            None
+        }
+    }
+
+    pub fn factor(&self, time: f64) -> f64 {
+        let phase = (time / self.period * std::f64::consts::TAU).cos();
+        self.low + (1.0 - self.low) * (0.5 + 0.5 * phase)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub map: Map,
+    pub cycle: Option<Cycle>,
+}
+
+impl Layer {
+    pub fn sample(&self, x: f64, y: f64, time: f64) -> f64 {
+        let factor = self.cycle.as_ref().map_or(1.0, |cycle| cycle.factor(time));
+        (self.map.sample(x, y) * factor).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Environment {
+    pub light: Layer,
+    pub temperature: Layer,
+    pub nutrients: Layer,
+    pub currents: Vec<Current>,
+    pub time: f64,
+}
+
+/// Sunlight from above, warm water on the right, a few rich patches, and a
+/// slow whirl in the middle.
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            light: Layer {
+                map: Map::Gradient {
+                    direction: Vec2::new(0.0, 1.0),
+                    start: 1.0,
+                    end: 0.2,
+                },
+                cycle: Cycle::new(DAY_LENGTH, NIGHT_LIGHT),
+            },
+            temperature: Layer {
+                map: Map::Gradient {
+                    direction: Vec2::new(1.0, 0.0),
+                    start: 0.3,
+                    end: 1.0,
+                },
+                cycle: Cycle::new(SEASON_LENGTH, WINTER_TEMPERATURE),
+            },
+            nutrients: Layer {
+                map: Map::Grid {
+                    columns: 4,
+                    rows: 3,
+                    values: vec![
+                        0.2, 0.8, 0.3, 0.1, //
+                        0.5, 0.1, 0.2, 0.9, //
+                        1.0, 0.4, 0.6, 0.3, //
+                    ],
+                },
+                cycle: None,
+            },
+            currents: vec![Current::Vortex {
+                center: Vec2::new(MAX_X / 2.0, MAX_Y / 2.0),
+                speed: VORTEX_SPEED,
+                core_radius: 150.0,
+            }],
+            time: 0.0,
+        }
+    }
+}
+
+impl Environment {
+    pub fn advance(&mut self, delta_time: f64) {
+        self.time += delta_time;
+    }
+
+    pub fn light(&self, x: f64, y: f64) -> f64 {
+        self.light.sample(x, y, self.time)
+    }
+
+    pub fn temperature(&self, x: f64, y: f64) -> f64 {
+        self.temperature.sample(x, y, self.time)
+    }
+
+    pub fn nutrients(&self, x: f64, y: f64) -> f64 {
+        self.nutrients.sample(x, y, self.time)
+    }
+
+    /// Velocity of the water at a point.
+    pub fn current(&self, x: f64, y: f64) -> Vec2 {
+        self.currents
+            .iter()
+            .fold(Vec2::zero(), |sum, current| sum + current.sample(x, y))
+    }
+
+    /// Photosynthesis needs light.
+    pub fn growth_factor(&self, x: f64, y: f64) -> f64 {
+        self.light(x, y)
+    }
+
+    /// Swimming in cold water is harder.
+    pub fn move_cost_factor(&self, x: f64, y: f64) -> f64 {
+        1.0 + COLD_MOVE_COST * (1.0 - self.temperature(x, y))
+    }
+
+    /// So is raising offspring in poor water.
+    pub fn split_cost_factor(&self, x: f64, y: f64) -> f64 {
+        1.0 + POOR_SPLIT_COST * (1.0 - self.nutrients(x, y))
+    }
+}
@@ -6,38 +6,76 @@ use rand_chacha::ChaCha20Rng;
 
 use crate::{
     color::Color,
+    environment::Environment,
     lang::{Fraction, Program},
+    ledger::EnergyLedger,
+    physics::coordinate_delta,
     vec2::Vec2,
-    BASE_SPLIT_COST, DIE_ON_AMBITIOUS_BABY, MUTATION_RATE, SPLIT_COST_FACTOR,
+    BASE_SPLIT_COST, BITE_EFFICIENCY, BITE_RATE, BITE_REACH, BOUNDARY_MODE, DIE_ON_AMBITIOUS_BABY,
+    MAX_BITE_FRACTION, MAX_X, MAX_Y, MUTATION_RATE, PREDATION_MODE, SPLIT_COST_FACTOR,
 };
 
 pub type Energy = NotNan<f64>;
 
+/// Hands out fish ids, counting up from 0. It is part of the `State`, so a
+/// run replayed from a snapshot gives its new fishes the same ids again.
+#[derive(Clone, Debug, Default)]
+pub struct FishIds {
+    next: u64,
+}
+
+impl FishIds {
+    /// A fresh id, different from every id handed out before.
+    pub fn take(&mut self) -> u64 {
+        let id = self.next;
+        self.next += 1;
+        id
+    }
+}
+
 #[derive(Clone)]
 pub struct Fish {
+    pub id: u64,
+    /// Id of the first fish of this fish's lineage.
+    pub founder: u64,
     pub x: f64,
     pub y: f64,
     pub velocity: Vec2,
 
     pub energy: Energy,
+    /// Most energy the fish has had since it was born or last split, the size
+    /// of the body it leaves behind.
+    pub peak_energy: Energy,
     pub program: Program,
+    /// `program.shape_hash()`, kept so it isn't recomputed every frame.
+    pub program_hash: u64,
     pub color: Color,
     pub is_man_made: bool,
     pub tag: Option<String>,
+    /// Ticks lived so far.
+    pub age: u64,
+    /// What the program decided to do last tick.
+    pub last_action: Action,
 }
 
 impl Fish {
-    #[allow(dead_code)]
-    pub fn new(x: f64, y: f64, energy: Energy, program: Program) -> Self {
+    #[cfg(test)]
+    pub fn new(id: u64, x: f64, y: f64, energy: Energy, program: Program) -> Self {
         Fish {
+            id,
+            founder: id,
             x,
             y,
             energy,
+            peak_energy: energy,
+            program_hash: program.shape_hash(),
             program,
             velocity: Vec2::new(0.0, 0.0),
             color: Color::GREEN,
             is_man_made: false,
             tag: None,
+            age: 0,
+            last_action: Action::Pass,
         }
     }
 }
@@ -60,13 +98,13 @@ impl Fish {
     }
     // Verplaatsingsvector naar. Ha. Blub. Blub. I'm coming to get you.
     pub fn displacement_to(&self, other: &Fish) -> Vec2 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        return Vec2::new(dx, dy);
+        self.displacement_to_point(other.x, other.y)
     }
 
-    pub fn momentum(&self) -> Vec2 {
-        self.velocity * self.mass()
+    pub fn displacement_to_point(&self, x: f64, y: f64) -> Vec2 {
+        let dx = coordinate_delta(self.x, x, MAX_X, BOUNDARY_MODE);
+        let dy = coordinate_delta(self.y, y, MAX_Y, BOUNDARY_MODE);
+        Vec2::new(dx, dy)
     }
 
     pub fn direction_to(&self, other: &Fish) -> Vec2 {
@@ -108,11 +146,26 @@ pub enum Action {
     Move(Vec2),
     SetVelocity(Vec2, Fraction),
     Split(Vec2, Fraction),
+    /// Bite the fish with this index, as hard as the fraction says.
+    Bite(usize, Fraction),
+}
+
+/// How fishes get energy out of other fishes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PredationMode {
+    /// A fish covering another fish's centre swallows it whole.
+    Cover,
+    /// Fishes take bites out of each other with the `Bite` action.
+    Bite,
+    Both,
 }
 
 pub struct FishControl<'a> {
     pub fishes: &'a mut Vec<Fish>,
     pub controls: &'a mut Vec<Control>,
+    pub environment: &'a Environment,
+    pub ledger: &'a mut EnergyLedger,
+    pub ids: &'a mut FishIds,
 }
 
 impl<'a> FishControl<'a> {
@@ -126,23 +179,35 @@ impl<'a> FishControl<'a> {
         let fish = &mut self.fishes[fish_index];
 
         let direction = force_per_kg.normalized();
+        let program = if rng.gen_range(0.0..1.0) < MUTATION_RATE {
+            fish.program.mutated(rng)
+        } else {
+            fish.program.clone()
+        };
         let child = Fish {
+            id: self.ids.take(),
+            founder: fish.founder,
             x: fish.x + direction.x * fish.radius() * 1.5,
             y: fish.y + direction.y * fish.radius() * 1.5,
             energy: fish.energy * mass_fraction,
-            program: if rng.gen_range(0.0..1.0) < MUTATION_RATE {
-                fish.program.mutated(rng)
-            } else {
-                fish.program.clone()
-            },
+            peak_energy: fish.energy * mass_fraction,
+            program_hash: program.shape_hash(),
+            program,
             velocity: Vec2::zero(),
             color: fish.color.mutate(rng),
             is_man_made: fish.is_man_made,
             tag: fish.tag.clone(),
+            age: 0,
+            last_action: Action::Pass,
         };
-        let cost: N64 = child.energy * SPLIT_COST_FACTOR + BASE_SPLIT_COST;
+        let cost: N64 = (child.energy * SPLIT_COST_FACTOR + BASE_SPLIT_COST)
+            * self.environment.split_cost_factor(fish.x, fish.y);
         if fish.energy > cost {
             fish.energy -= cost;
+            fish.peak_energy = fish.energy;
+            // The child's energy came out of the parent, the rest is lost
+            self.ledger.offspring += child.mass();
+            self.ledger.split_cost += cost.into_inner() - child.mass();
             let (x, y) = (force_per_kg.x, force_per_kg.y);
             let child_force = Vec2::new(x * child.mass(), y * child.mass());
             self.fishes.push(child);
@@ -150,10 +215,33 @@ impl<'a> FishControl<'a> {
         } else {
             if DIE_ON_AMBITIOUS_BABY {
                 fish.energy -= cost;
+                self.ledger.failed_split += cost.into_inner();
             }
         }
     }
 
+    /// Take a bite out of a fish within reach. Bites never take more than
+    /// `MAX_BITE_FRACTION` of the victim, so it survives, and only
+    /// `BITE_EFFICIENCY` of what is bitten off ends up in the biter.
+    pub fn bite(&mut self, fish_index: usize, target_index: usize, size: f64, delta_time: f64) {
+        if target_index == fish_index || target_index >= self.fishes.len() {
+            return;
+        }
+        let (biter, victim) = (&self.fishes[fish_index], &self.fishes[target_index]);
+        let gap = biter.distance(victim) - biter.radius() - victim.radius();
+        if gap > BITE_REACH || victim.energy <= 0.0 || biter.energy <= 0.0 {
+            return;
+        }
+
+        let amount = (size * BITE_RATE * biter.surface_area() * delta_time)
+            .min(victim.mass() * MAX_BITE_FRACTION);
+        let gained = amount * BITE_EFFICIENCY;
+        self.fishes[target_index].energy -= amount;
+        self.fishes[fish_index].energy += gained;
+        self.ledger.bitten += gained;
+        self.ledger.digestion += amount - gained;
+    }
+
     pub fn reproduce(&mut self, rng: &mut ChaCha20Rng, fish_index: usize) {
         let direction = Vec2::random_normalized(rng);
         let force_per_kg = direction * 10.0;
@@ -170,11 +258,13 @@ pub fn execute_fish_action(
 ) {
     use Action::*;
     let fish = &mut fish_control.fishes[fish_index];
+    let move_cost_factor = fish_control.environment.move_cost_factor(fish.x, fish.y);
     match action {
         Move(force_per_kg) => {
             let force = force_per_kg * fish.mass();
-            let cost = force.length() * delta_time;
+            let cost = force.length() * delta_time * move_cost_factor;
             fish.energy -= cost;
+            fish_control.ledger.movement += cost;
 
             fish_control.controls[fish_index].force += force;
 
@@ -193,7 +283,7 @@ pub fn execute_fish_action(
             let impulse_needed = (target_velocity - fish.velocity) * fish.mass();
 
             let mut force_needed = impulse_needed / delta_time;
-            let mut cost: N64 = N64::from(force_needed.length() * delta_time);
+            let mut cost: N64 = N64::from(force_needed.length() * delta_time * move_cost_factor);
             let cost_max: N64 = fish.energy * N64::from(max_energy_ratio);
             if cost > cost_max {
                 // bound impulse by allocated energy
@@ -201,6 +291,7 @@ pub fn execute_fish_action(
                 cost = cost_max;
             }
             fish.energy -= cost;
+            fish_control.ledger.movement += cost.into_inner();
             fish_control.controls[fish_index].force += force_needed;
         }
         Split(force_per_kg, mass_fraction) => {
@@ -209,6 +300,66 @@ pub fn execute_fish_action(
             fish_control.split_fish(rng, fish_index, force_per_kg, mass_fraction.to_f64());
             // }
         }
+        Bite(target_index, size) => {
+            if PREDATION_MODE != PredationMode::Cover {
+                fish_control.bite(fish_index, target_index, size.to_f64(), delta_time);
+            }
+        }
         Pass => (),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fish(id: u64, x: f64, energy: f64) -> Fish {
+        Fish::new(id, x, 100.0, NotNan::from_inner(energy), Program::empty())
+    }
+
+    #[test]
+    fn bites_within_reach() {
+        let biter = fish(0, 100.0, 1000.0);
+        let victim = fish(1, 0.0, 100.0);
+        let touching = 100.0 + biter.radius() + victim.radius();
+        let mut fishes = vec![biter, victim];
+        let mut controls = Vec::new();
+        let environment = Environment::default();
+        let mut ledger = EnergyLedger::default();
+        let mut ids = FishIds::default();
+        let mut fish_control = FishControl {
+            fishes: &mut fishes,
+            controls: &mut controls,
+            environment: &environment,
+            ledger: &mut ledger,
+            ids: &mut ids,
+        };
+
+        // Out of reach
+        fish_control.fishes[1].move_to(touching + BITE_REACH + 1.0, 100.0);
+        fish_control.bite(0, 1, 1.0, 1.0);
+        assert_eq!(fish_control.fishes[1].energy, 100.0);
+
+        // A small bite, proportional to the biter's surface
+        fish_control.fishes[1].move_to(touching + BITE_REACH - 1.0, 100.0);
+        let size = 0.1;
+        let delta_time = 0.001;
+        let expected = size * BITE_RATE * fish_control.fishes[0].surface_area() * delta_time;
+        fish_control.bite(0, 1, size, delta_time);
+        let taken = 100.0 - fish_control.fishes[1].energy.into_inner();
+        assert!((taken - expected).abs() < 1e-9);
+
+        // A huge bite only takes a fraction of the victim
+        let before = fish_control.fishes[1].mass();
+        fish_control.bite(0, 1, 1.0, 1000.0);
+        let taken = before - fish_control.fishes[1].mass();
+        assert!((taken - before * MAX_BITE_FRACTION).abs() < 1e-9);
+
+        // The ledger splits everything taken between the biter and digestion
+        let total_taken = 100.0 - fishes[1].mass();
+        let gained = fishes[0].mass() - 1000.0;
+        assert!((ledger.bitten - gained).abs() < 1e-9);
+        assert!((ledger.bitten - total_taken * BITE_EFFICIENCY).abs() < 1e-9);
+        assert!((ledger.bitten + ledger.digestion - total_taken).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,97 @@
+// Fish food. Particles drift into the aquarium at random spots and are
+// eaten by the first fish to cover them.
+
+use decorum::NotNan;
+use rand::Rng;
+use rand_chacha::ChaCha20Rng;
+use rand_distr::{Distribution, Poisson};
+
+use crate::environment::Environment;
+use crate::fish::{Energy, Fish};
+use crate::vec2::Vec2;
+use crate::{FOOD_PARTICLE_ENERGY, FOOD_SPAWN_RATE, MAX_FOOD_PARTICLES, MAX_X, MAX_Y};
+
+#[derive(Clone, Debug)]
+pub struct FoodParticle {
+    pub x: f64,
+    pub y: f64,
+    pub energy: Energy,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Food {
+    pub particles: Vec<FoodParticle>,
+}
+
+impl Food {
+    pub fn new() -> Self {
+        Food::default()
+    }
+
+    /// Scatter new particles, on average `FOOD_SPAWN_RATE` per second in the
+    /// richest water, until there are `MAX_FOOD_PARTICLES`. Poorer water gets
+    /// proportionally less. Returns the energy of the new particles.
+    pub fn spawn(
+        &mut self,
+        rng: &mut ChaCha20Rng,
+        environment: &Environment,
+        delta_time: f64,
+    ) -> f64 {
+        if FOOD_SPAWN_RATE <= 0.0 {
+            return 0.0;
+        }
+        let distr = Poisson::new(FOOD_SPAWN_RATE * delta_time).unwrap();
+        let n_particles = distr.sample(rng) as usize;
+        let room = MAX_FOOD_PARTICLES.saturating_sub(self.particles.len());
+        let mut spawned = 0.0;
+        for _ in 0..n_particles.min(room) {
+            let x = rng.gen_range(0.0..MAX_X);
+            let y = rng.gen_range(0.0..MAX_Y);
+            if rng.gen_range(0.0..1.0) < environment.nutrients(x, y) {
+                self.particles.push(FoodParticle {
+                    x,
+                    y,
+                    energy: NotNan::from_inner(FOOD_PARTICLE_ENERGY),
+                });
+                spawned += FOOD_PARTICLE_ENERGY;
+            }
+        }
+        spawned
+    }
+
+    pub fn add(&mut self, x: f64, y: f64, energy: Energy) {
+        self.particles.push(FoodParticle { x, y, energy });
+    }
+
+    /// Let fishes eat the particles they cover. Fishes go in order, so sort
+    /// them by size first if big fish should get first pick. Returns the
+    /// energy eaten.
+    pub fn feed(&mut self, fishes: &mut [Fish]) -> f64 {
+        let mut eaten = 0.0;
+        for fish in fishes.iter_mut() {
+            let radius = fish.radius();
+            self.particles.retain(|particle| {
+                if fish.displacement_to_point(particle.x, particle.y).length() < radius {
+                    fish.energy += particle.energy;
+                    eaten += particle.energy.into_inner();
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        eaten
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        self.particles.iter().map(|p| p.energy.into_inner()).sum()
+    }
+
+    /// The particle closest to the fish, and the displacement towards it.
+    pub fn nearest(&self, fish: &Fish) -> Option<(&FoodParticle, Vec2)> {
+        self.particles
+            .iter()
+            .map(|particle| (particle, fish.displacement_to_point(particle.x, particle.y)))
+            .min_by_key(|(_, displacement)| NotNan::from_inner(displacement.length()))
+    }
+}
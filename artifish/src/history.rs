@@ -0,0 +1,65 @@
+// Recent population numbers, for graphs in the HUD. Unlike the metrics, old
+// samples are dropped.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::fish::Fish;
+
+#[derive(Clone, Debug)]
+pub struct HistorySample {
+    pub tick: u64,
+    pub population: usize,
+    pub total_energy: f64,
+    /// Number of fishes with each tag, e.g. per kind of angel.
+    pub tags: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct History {
+    /// Ticks between samples.
+    pub interval: u64,
+    /// Maximum number of samples kept.
+    pub capacity: usize,
+    pub samples: VecDeque<HistorySample>,
+}
+
+impl History {
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        History {
+            interval,
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, fishes: &[Fish], total_energy: f64) {
+        if !tick.is_multiple_of(self.interval) {
+            return;
+        }
+        let mut tags = BTreeMap::new();
+        for tag in fishes.iter().filter_map(|fish| fish.tag.as_ref()) {
+            *tags.entry(tag.clone()).or_insert(0) += 1;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(HistorySample {
+            tick,
+            population: fishes.len(),
+            total_energy,
+            tags,
+        });
+    }
+
+    /// Every tag seen in the kept samples.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .samples
+            .iter()
+            .flat_map(|sample| sample.tags.keys().map(|tag| tag.as_str()))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+}
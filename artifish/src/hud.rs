@@ -0,0 +1,442 @@
+// What's drawn in the window: the aquarium, debug overlays, and on top of
+// that the status line, graphs, colour legend and the side panel.
+
+use std::collections::HashMap;
+
+use graphics::ellipse::Border;
+use opengl_graphics::{GlGraphics, GlyphCache};
+use piston::input::RenderArgs;
+
+use crate::color::Color;
+use crate::fish::Fish;
+use crate::history::History;
+use crate::obstacles::Obstacle;
+use crate::vec2::Vec2;
+use crate::App;
+use crate::{GRAPH_HEIGHT, GRAPH_WIDTH, MAX_X, MAX_Y, MOVE_SPEED, N_TICKS, PANEL_FONT_SIZE};
+use crate::{PANEL_WIDTH, VECTOR_SECONDS};
+
+/// What the side panel says about a fish.
+fn describe_fish(fish: &Fish) -> Vec<String> {
+    let mut lines = vec![
+        match &fish.tag {
+            Some(tag) => format!("Fish #{} \"{}\"", fish.id, tag),
+            None => format!("Fish #{}", fish.id),
+        },
+        format!("founder: #{}", fish.founder),
+        format!("energy: {:.1}", fish.energy.into_inner()),
+        format!("radius: {:.2}", fish.radius()),
+        format!("velocity: ({:.3}, {:.3})", fish.velocity.x, fish.velocity.y),
+        format!("man made: {}", fish.is_man_made),
+        format!(
+            "age: {} ticks ({:.1}s)",
+            fish.age,
+            fish.age as f64 / N_TICKS as f64
+        ),
+        format!("last action: {:.2?}", fish.last_action),
+        format!(
+            "program: size {}, depth {}",
+            fish.program.size(),
+            fish.program.depth()
+        ),
+    ];
+    lines.extend(fish.program.tree_lines());
+    lines
+}
+
+/// Plot the population, fishes per tag and total energy over time, in two
+/// graphs stacked below `transform`.
+fn draw_history(
+    history: &History,
+    transform: graphics::math::Matrix2d,
+    glyph_cache: &mut GlyphCache,
+    gl: &mut GlGraphics,
+) {
+    use graphics::*;
+
+    let samples = &history.samples;
+    let tags = history.tags();
+    let mut counts: Vec<(String, Color, Vec<f64>)> = vec![(
+        "fishes".to_owned(),
+        Color::WHITE,
+        samples.iter().map(|s| s.population as f64).collect(),
+    )];
+    for (i, tag) in tags.iter().enumerate() {
+        let values = samples
+            .iter()
+            .map(|s| *s.tags.get(*tag).unwrap_or(&0) as f64)
+            .collect();
+        let hue = (i + 1) as f64 / (tags.len() + 1) as f64;
+        counts.push((tag.to_string(), Color::from_hue(hue), values));
+    }
+    let energy = vec![(
+        "energy".to_owned(),
+        Color::new([1.0, 0.9, 0.2, 1.0]),
+        samples.iter().map(|s| s.total_energy).collect(),
+    )];
+
+    let line_height = PANEL_FONT_SIZE as f64 * 1.3;
+    if let (Some(first), Some(last)) = (samples.front(), samples.back()) {
+        let span = format!("ticks {} to {}", first.tick, last.tick);
+        let t = transform.trans(GRAPH_WIDTH - 150.0, -4.0);
+        text(
+            Color::WHITE.into(),
+            PANEL_FONT_SIZE,
+            &span,
+            glyph_cache,
+            t,
+            gl,
+        )
+        .unwrap();
+    }
+    for (g, series) in [counts, energy].iter().enumerate() {
+        let top = g as f64 * (GRAPH_HEIGHT + line_height * 2.0);
+        let area = [0.0, top, GRAPH_WIDTH, GRAPH_HEIGHT];
+        rectangle([0.0, 0.0, 0.0, 0.6], area, transform, gl);
+        Rectangle::new_border(Color::WHITE.darken(0.5).into(), 0.5).draw(
+            area,
+            &Default::default(),
+            transform,
+            gl,
+        );
+
+        // Series in one graph share a scale
+        let max = series
+            .iter()
+            .flat_map(|(_, _, values)| values.iter().copied())
+            .fold(0.0, f64::max)
+            .max(1.0);
+        let step = GRAPH_WIDTH / (history.capacity - 1).max(1) as f64;
+        let mut label_x = 0.0;
+        for (name, color, values) in series.iter() {
+            let points: Vec<[f64; 2]> = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| [i as f64 * step, top + GRAPH_HEIGHT * (1.0 - v / max)])
+                .collect();
+            for pair in points.windows(2) {
+                let segment = [pair[0][0], pair[0][1], pair[1][0], pair[1][1]];
+                line((*color).into(), 0.5, segment, transform, gl);
+            }
+
+            let label = format!("{} {:.0}", name, values.last().unwrap_or(&0.0));
+            let t = transform.trans(label_x, top + GRAPH_HEIGHT + line_height);
+            text((*color).into(), PANEL_FONT_SIZE, &label, glyph_cache, t, gl).unwrap();
+            label_x += (label.len() as f64 + 2.0) * PANEL_FONT_SIZE as f64 * 0.5;
+        }
+    }
+}
+
+impl<'a> App<'a> {
+    pub fn render(&mut self, args: &RenderArgs) {
+        use graphics::*;
+
+        self.camera.viewport = args.window_size;
+        self.camera.track(&self.state.fishes);
+        let status = self.status_line();
+        let color_mode = self.color_mode;
+        let (show_trails, show_vectors, show_links) =
+            (self.show_trails, self.show_vectors, self.show_links);
+        let trails = &self.trails;
+        let traces = &self.state.traces;
+        let history = if self.show_graphs {
+            Some(&self.state.history)
+        } else {
+            None
+        };
+        let camera = &self.camera;
+
+        let fishes = &self.state.fishes;
+        let selected = self
+            .selected
+            .and_then(|id| fishes.iter().find(|fish| fish.id == id));
+        let food = &self.state.food;
+        let obstacles = &self.state.obstacles;
+        let corpses = &self.state.corpses;
+
+        let glyph_cache = &mut self.glyph_cache;
+
+        self.gl.draw(args.viewport(), |c, gl| {
+            // Clear the screen.
+            clear(Color::BLACK.into(), gl);
+
+            let screen = c.transform;
+            let world = camera.transform(screen);
+
+            let walls = [0.0, 0.0, MAX_X, MAX_Y];
+            Rectangle::new_border(Color::WHITE.darken(0.7).into(), 1.0 / camera.zoom).draw(
+                walls,
+                &Default::default(),
+                world,
+                gl,
+            );
+
+            for obstacle in obstacles.iter() {
+                let rock_color = Color::new([0.4, 0.35, 0.3, 1.0]);
+                match obstacle {
+                    Obstacle::Circle { center, radius } => {
+                        let rock = ellipse::circle(center.x, center.y, *radius);
+                        ellipse(rock_color.into(), rock, world, gl);
+                    }
+                    Obstacle::Polygon { vertices } => {
+                        let points: Vec<[f64; 2]> = vertices.iter().map(|v| [v.x, v.y]).collect();
+                        polygon(rock_color.into(), &points, world, gl);
+                    }
+                }
+            }
+
+            for corpse in corpses.corpses.iter() {
+                if !camera.is_visible(corpse.x, corpse.y, corpse.radius()) {
+                    continue;
+                }
+                let body = ellipse::circle(corpse.x, corpse.y, corpse.radius());
+                ellipse(corpse.color.into(), body, world, gl);
+            }
+
+            for particle in food.particles.iter() {
+                if !camera.is_visible(particle.x, particle.y, 1.5) {
+                    continue;
+                }
+                let dot = ellipse::circle(particle.x, particle.y, 1.5);
+                ellipse(Color::WHITE.into(), dot, world, gl);
+            }
+
+            for fish in fishes.iter().rev() {
+                if !camera.is_visible(fish.x, fish.y, fish.radius()) {
+                    continue;
+                }
+
+                let fish_color = color_mode.fish_color(fish);
+                let fish_color_dark = fish_color.darken(0.5);
+                let cell = ellipse::circle(fish.x, fish.y, fish.radius());
+                let cell_border = Border {
+                    color: fish_color_dark.into(),
+                    radius: 1.0,
+                };
+                Ellipse::new(fish_color.into()).border(cell_border).draw(
+                    cell,
+                    &Default::default(),
+                    world,
+                    gl,
+                );
+
+                let center = ellipse::circle(fish.x, fish.y, 1.5);
+                ellipse(fish_color_dark.into(), center, world, gl);
+
+                if let Some(ref tag) = fish.tag {
+                    text(
+                        Color::WHITE.into(),
+                        20,
+                        tag,
+                        glyph_cache,
+                        world.trans(fish.x, fish.y),
+                        gl,
+                    )
+                    .unwrap();
+                }
+            }
+
+            let pixel = 1.0 / camera.zoom;
+            let by_id: HashMap<u64, &Fish> = fishes.iter().map(|fish| (fish.id, fish)).collect();
+
+            if show_trails {
+                for (id, trail) in trails.iter() {
+                    let fish = match by_id.get(id) {
+                        Some(fish) => fish,
+                        None => continue,
+                    };
+                    let [r, g, b, _] = color_mode.fish_color(fish).inner;
+                    let n = trail.len();
+                    for (k, (from, to)) in trail.iter().zip(trail.iter().skip(1)).enumerate() {
+                        // Don't draw jumps across a wrapping aquarium
+                        if (*to - *from).length() > MAX_X.min(MAX_Y) / 2.0 {
+                            continue;
+                        }
+                        let alpha = 0.6 * (k + 1) as f32 / n as f32;
+                        line(
+                            [r, g, b, alpha],
+                            pixel,
+                            [from.x, from.y, to.x, to.y],
+                            world,
+                            gl,
+                        );
+                    }
+                }
+            }
+
+            for trace in traces.iter() {
+                let fish = match by_id.get(&trace.fish_id) {
+                    Some(fish) => fish,
+                    None => continue,
+                };
+                if show_vectors && camera.is_visible(fish.x, fish.y, fish.radius()) {
+                    let velocity = fish.velocity * MOVE_SPEED * VECTOR_SECONDS;
+                    let acceleration = trace.force / fish.mass() * MOVE_SPEED * VECTOR_SECONDS;
+                    for (vector, color) in [(velocity, Color::WHITE), (acceleration, Color::RED)] {
+                        let end = [fish.x + vector.x, fish.y + vector.y];
+                        line(
+                            color.into(),
+                            pixel,
+                            [fish.x, fish.y, end[0], end[1]],
+                            world,
+                            gl,
+                        );
+                    }
+                }
+                if show_links {
+                    for id in trace.sensed.iter() {
+                        if let Some(target) = by_id.get(id) {
+                            let to = Vec2::new(fish.x, fish.y) + fish.displacement_to(target);
+                            line(
+                                [1.0, 1.0, 0.0, 0.5],
+                                pixel,
+                                [fish.x, fish.y, to.x, to.y],
+                                world,
+                                gl,
+                            );
+                        }
+                    }
+                }
+            }
+
+            if let Some(fish) = selected {
+                let ring = ellipse::circle(fish.x, fish.y, fish.radius() + 3.0 / camera.zoom);
+                Ellipse::new_border(Color::WHITE.into(), 1.0 / camera.zoom).draw(
+                    ring,
+                    &Default::default(),
+                    world,
+                    gl,
+                );
+            }
+
+            // let t = screen.trans(100.0, 100.0);
+            // text(Color::RED.into(), 100, "tetten", glyph_cache, t, gl).unwrap();
+
+            let [width, height] = args.window_size;
+            let t = screen.trans(width - 100.0, height - 100.0);
+            text(
+                Color::RED.into(),
+                30,
+                &fishes.len().to_string(),
+                glyph_cache,
+                t,
+                gl,
+            )
+            .unwrap();
+
+            text(
+                Color::WHITE.into(),
+                PANEL_FONT_SIZE,
+                &status,
+                glyph_cache,
+                screen.trans(10.0, 20.0),
+                gl,
+            )
+            .unwrap();
+
+            if let Some(history) = history {
+                draw_history(history, screen.trans(10.0, 50.0), glyph_cache, gl);
+            }
+
+            let line_height = PANEL_FONT_SIZE as f64 * 1.3;
+            let legend = color_mode.legend();
+            let legend_top = height - line_height * (legend.len() + 1) as f64;
+            let title = format!("colour: {} (C or 1-7 to change)", color_mode.name());
+            text(
+                Color::WHITE.into(),
+                PANEL_FONT_SIZE,
+                &title,
+                glyph_cache,
+                screen.trans(10.0, legend_top),
+                gl,
+            )
+            .unwrap();
+            for (i, (swatch, label)) in legend.iter().enumerate() {
+                let y = legend_top + line_height * (i + 1) as f64;
+                let mut x = 10.0;
+                if let Some(swatch) = swatch {
+                    let square = [x, y - PANEL_FONT_SIZE as f64 * 0.8, 10.0, 10.0];
+                    rectangle((*swatch).into(), square, screen, gl);
+                    x += 16.0;
+                }
+                text(
+                    Color::WHITE.into(),
+                    PANEL_FONT_SIZE,
+                    label,
+                    glyph_cache,
+                    screen.trans(x, y),
+                    gl,
+                )
+                .unwrap();
+            }
+
+            if let Some(fish) = selected {
+                let [width, height] = args.window_size;
+                let panel = [width - PANEL_WIDTH, 0.0, PANEL_WIDTH, height];
+                rectangle([0.0, 0.0, 0.0, 0.8], panel, screen, gl);
+
+                let max_lines = ((height - line_height) / line_height).max(1.0) as usize;
+                let mut lines = describe_fish(fish);
+                if lines.len() > max_lines {
+                    lines.truncate(max_lines - 1);
+                    lines.push("...".to_owned());
+                }
+                for (i, line) in lines.iter().enumerate() {
+                    let y = line_height * (i + 1) as f64;
+                    let t = screen.trans(width - PANEL_WIDTH + 10.0, y);
+                    text(
+                        Color::WHITE.into(),
+                        PANEL_FONT_SIZE,
+                        line,
+                        glyph_cache,
+                        t,
+                        gl,
+                    )
+                    .unwrap();
+                }
+            }
+        });
+    }
+
+    /// One line summing up how the simulation is running.
+    fn status_line(&self) -> String {
+        let mode = if self.paused {
+            "paused".to_owned()
+        } else if self.fast_mode {
+            "fast".to_owned()
+        } else {
+            format!("x{}", self.speed)
+        };
+        let overlays = [
+            (self.show_trails, "trails"),
+            (self.show_vectors, "vectors"),
+            (self.show_links, "links"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(" ");
+        let timeline = match self.rewind.position() {
+            Some(position) => format!(
+                "rewound {}/{}  seed {}",
+                position + 1,
+                self.rewind.len(),
+                self.seed
+            ),
+            None => format!("seed {}", self.seed),
+        };
+        let status = format!(
+            "tick {}  {:.1} ticks/s  {}  {}  {}",
+            self.state.tick,
+            self.clock.tps(),
+            mode,
+            timeline,
+            overlays
+        );
+        match &self.tag_input {
+            Some(tag) => format!("{}  tag: {}_", status, tag),
+            None => status,
+        }
+    }
+}
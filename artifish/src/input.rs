@@ -0,0 +1,222 @@
+// Keyboard and mouse controls for the window.
+
+use piston::input::{Button, Key, MouseButton};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::angels::AngelKind;
+use crate::coloring::ColorMode;
+use crate::state::State;
+use crate::App;
+use crate::{CLICK_DISTANCE, GOD_ENERGY_STEP, MAX_SPEED, MIN_SPEED, PAN_STEP, ZOOM_STEP};
+
+impl<'a> App<'a> {
+    /// Keyboard and mouse controls:
+    /// - arrows or dragging with the left mouse button pan, +/- or scrolling
+    ///   zooms, Home shows the whole aquarium, F follows a fish
+    /// - clicking a fish shows it in the side panel
+    /// - space pauses, N steps one tick while paused, [ and ] change the
+    ///   speed, Tab toggles fast mode
+    /// - C or the number keys pick how fishes are coloured
+    /// - G shows graphs of the population over time
+    /// - T, V and L toggle trails, velocity and force vectors, and lines to
+    ///   the fishes each program looked at
+    /// - god tools: Q, W and E add a smartie, toast niet kannibaal or
+    ///   ass is grass angel at the cursor, R a random fish. Dragging with the
+    ///   right mouse button moves a fish. For the selected fish, Delete
+    ///   removes it, K clones it, P and O give and take energy, and Return
+    ///   starts typing a new tag, confirmed with another Return
+    /// - , and . go back and forward through snapshots of the past, B
+    ///   starts a new timeline with a new seed from the one shown
+    pub fn press(&mut self, button: Button) {
+        if self.tag_input.is_some() {
+            self.edit_tag(button);
+            return;
+        }
+        let mode_keys = [
+            Key::D1,
+            Key::D2,
+            Key::D3,
+            Key::D4,
+            Key::D5,
+            Key::D6,
+            Key::D7,
+        ];
+        match button {
+            Button::Keyboard(Key::C) => self.color_mode = self.color_mode.next(),
+            Button::Keyboard(Key::T) => {
+                self.show_trails = !self.show_trails;
+                self.trails.clear();
+            }
+            Button::Keyboard(Key::V) => self.show_vectors = !self.show_vectors,
+            Button::Keyboard(Key::G) => self.show_graphs = !self.show_graphs,
+            Button::Keyboard(Key::L) => self.show_links = !self.show_links,
+            Button::Keyboard(key) if mode_keys.contains(&key) => {
+                let i = mode_keys.iter().position(|k| *k == key).unwrap();
+                self.color_mode = ColorMode::ALL[i];
+            }
+            Button::Keyboard(Key::Space) => self.paused = !self.paused,
+            Button::Keyboard(Key::N) if self.paused => {
+                self.tick();
+            }
+            Button::Keyboard(Key::LeftBracket) => {
+                self.speed = (self.speed / 2.0).max(MIN_SPEED);
+            }
+            Button::Keyboard(Key::RightBracket) => {
+                self.speed = (self.speed * 2.0).min(MAX_SPEED);
+            }
+            Button::Keyboard(Key::Tab) => self.fast_mode = !self.fast_mode,
+            Button::Keyboard(Key::Left) => self.camera.pan(PAN_STEP, 0.0),
+            Button::Keyboard(Key::Right) => self.camera.pan(-PAN_STEP, 0.0),
+            Button::Keyboard(Key::Up) => self.camera.pan(0.0, PAN_STEP),
+            Button::Keyboard(Key::Down) => self.camera.pan(0.0, -PAN_STEP),
+            Button::Keyboard(Key::Equals) => self.zoom(ZOOM_STEP),
+            Button::Keyboard(Key::Minus) => self.zoom(1.0 / ZOOM_STEP),
+            Button::Keyboard(Key::Home) => self.camera.reset(),
+            // Follow the fish under the cursor, or the biggest one
+            Button::Keyboard(Key::F) => {
+                let position = self.camera.screen_to_world(self.cursor);
+                self.camera.following = self
+                    .state
+                    .fish_at(position.x, position.y)
+                    .or_else(|| self.state.fishes.first())
+                    .map(|fish| fish.id);
+            }
+            Button::Mouse(MouseButton::Left) => {
+                self.dragging = true;
+                self.drag_distance = 0.0;
+            }
+            Button::Keyboard(Key::Comma) => {
+                self.paused = true;
+                if let Some(state) = self.rewind.back(&self.state) {
+                    self.show_snapshot(state);
+                }
+            }
+            Button::Keyboard(Key::Period) => {
+                if let Some(state) = self.rewind.forward() {
+                    self.show_snapshot(state);
+                }
+            }
+            Button::Keyboard(Key::B) => {
+                self.rewind.resume();
+                self.seed = rand::random();
+                self.state.rng = ChaCha20Rng::seed_from_u64(self.seed);
+            }
+            Button::Keyboard(Key::Q) => self.spawn_angel(AngelKind::Smartie),
+            Button::Keyboard(Key::W) => self.spawn_angel(AngelKind::ToastNietKannibaal),
+            Button::Keyboard(Key::E) => self.spawn_angel(AngelKind::AssIsGrass),
+            Button::Keyboard(Key::R) => {
+                let position = self.camera.screen_to_world(self.cursor);
+                self.selected = Some(self.state.spawn_random_fish(position.x, position.y));
+            }
+            Button::Mouse(MouseButton::Right) => {
+                let position = self.camera.screen_to_world(self.cursor);
+                self.dragged_fish = self
+                    .state
+                    .fish_at(position.x, position.y)
+                    .map(|fish| fish.id);
+            }
+            Button::Keyboard(Key::Delete) => {
+                if let Some(id) = self.selected.take() {
+                    self.state.remove_fish(id);
+                }
+            }
+            Button::Keyboard(Key::K) => {
+                if let Some(id) = self.selected {
+                    self.selected = self.state.clone_fish(id).or(self.selected);
+                }
+            }
+            Button::Keyboard(Key::P) => self.give_energy(GOD_ENERGY_STEP),
+            Button::Keyboard(Key::O) => self.give_energy(-GOD_ENERGY_STEP),
+            Button::Keyboard(Key::Return) => {
+                if let Some(fish) = self.selected.and_then(|id| self.state.fish_mut(id)) {
+                    self.tag_input = Some(fish.tag.clone().unwrap_or_default());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn release(&mut self, button: Button) {
+        if button == Button::Mouse(MouseButton::Right) {
+            self.dragged_fish = None;
+        }
+        if button == Button::Mouse(MouseButton::Left) {
+            self.dragging = false;
+            // Clicking selects the fish under the cursor, or clears the panel
+            if self.drag_distance < CLICK_DISTANCE {
+                let position = self.camera.screen_to_world(self.cursor);
+                self.selected = self
+                    .state
+                    .fish_at(position.x, position.y)
+                    .map(|fish| fish.id);
+            }
+        }
+    }
+
+    pub fn move_cursor(&mut self, position: [f64; 2]) {
+        if let Some(id) = self.dragged_fish {
+            let target = self.camera.screen_to_world(position);
+            if !self.state.move_fish(id, target.x, target.y) {
+                self.dragged_fish = None;
+            }
+        }
+        if self.dragging {
+            let dx = position[0] - self.cursor[0];
+            let dy = position[1] - self.cursor[1];
+            self.drag_distance += dx.hypot(dy);
+            self.camera.pan(dx, dy);
+        }
+        self.cursor = position;
+    }
+
+    pub fn scroll(&mut self, scroll: [f64; 2]) {
+        self.camera.zoom_at(ZOOM_STEP.powf(scroll[1]), self.cursor);
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        if let Some(tag) = &mut self.tag_input {
+            tag.extend(text.chars().filter(|c| !c.is_control()));
+        }
+    }
+
+    fn edit_tag(&mut self, button: Button) {
+        match button {
+            Button::Keyboard(Key::Backspace) => {
+                if let Some(tag) = &mut self.tag_input {
+                    tag.pop();
+                }
+            }
+            Button::Keyboard(Key::Return) => {
+                let tag = self.tag_input.take().filter(|tag| !tag.is_empty());
+                if let Some(id) = self.selected {
+                    self.state.set_tag(id, tag);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn show_snapshot(&mut self, mut state: State) {
+        // Snapshots don't have metrics, so keep the present's
+        std::mem::swap(&mut state.metrics, &mut self.state.metrics);
+        self.state = state;
+        self.trails.clear();
+    }
+
+    fn spawn_angel(&mut self, kind: AngelKind) {
+        let position = self.camera.screen_to_world(self.cursor);
+        self.selected = Some(self.state.spawn_angel(kind, position.x, position.y));
+    }
+
+    fn give_energy(&mut self, amount: f64) {
+        if let Some(id) = self.selected {
+            self.state.give_energy(id, amount);
+        }
+    }
+
+    fn zoom(&mut self, factor: f64) {
+        let middle = [self.camera.viewport[0] / 2.0, self.camera.viewport[1] / 2.0];
+        self.camera.zoom_at(factor, middle);
+    }
+}
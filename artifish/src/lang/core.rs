@@ -1,9 +1,21 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use rand::Rng;
 use rand_chacha::ChaCha20Rng;
 
-use super::expressions::ConstExpr;
-use super::generators::generate_action_expr;
+use super::generators::{generate_action_expr, generate_minimal_expr};
+use super::grammar::{grammar, GenerationMethod, Symbol};
+use crate::environment::Environment;
 use crate::fish::{Action, Fish};
+use crate::food::Food;
+use crate::obstacles::Obstacle;
+use crate::{
+    HOIST_MUTATION_RATE, MAX_MUTATION_ATTEMPTS, MAX_PROGRAM_DEPTH, MAX_PROGRAM_SIZE,
+    SHRINK_MUTATION_RATE,
+};
 
 // THE GREAT BEHAVIOURAL INTERPRETER
 #[derive(Clone)]
@@ -12,9 +24,9 @@ pub struct Program {
 }
 
 impl Program {
-    #[allow(dead_code)]
+    #[cfg(test)]
     pub fn empty() -> Self {
-        let root: Box<dyn Expr<Action>> = Box::new(ConstExpr {
+        let root: Box<dyn Expr<Action>> = Box::new(super::expressions::ConstExpr {
             value: Action::Pass,
         });
         Program { root: root.into() }
@@ -28,22 +40,123 @@ impl Program {
         }
     }
 
+    /// Ramped half-and-half initialisation: the depth limits are spread evenly
+    /// over `min_depth..=max_depth`, and at every depth half of the programs
+    /// are grown freely and half are full trees.
+    pub fn ramped_half_and_half(
+        rng: &mut ExprRng,
+        count: usize,
+        min_depth: u64,
+        max_depth: u64,
+    ) -> Vec<Self> {
+        let min_depth = min_depth.max(grammar().min_depth(Symbol::Action));
+        let max_depth = max_depth.max(min_depth);
+        let num_depths = (max_depth - min_depth + 1) as usize;
+
+        (0..count)
+            .map(|i| {
+                let depth = min_depth + (i % num_depths) as u64;
+                let method = if (i / num_depths).is_multiple_of(2) {
+                    GenerationMethod::Grow
+                } else {
+                    GenerationMethod::Full
+                };
+                let mut program = Program {
+                    root: ExprSlot::new(grammar().generate_action(rng, depth, method)),
+                };
+                program.repair(rng);
+                program
+            })
+            .collect()
+    }
+
     pub fn run(&self, state: &InterpreterState) -> Action {
         self.root.inner.eval(state)
     }
 
-    #[allow(dead_code)]
     pub fn size(&self) -> u64 {
-        return self.root.size();
+        self.root.size()
+    }
+
+    pub fn depth(&self) -> u64 {
+        self.root.depth()
     }
 
+    pub fn is_within_limits(&self) -> bool {
+        self.size() <= MAX_PROGRAM_SIZE && self.depth() <= MAX_PROGRAM_DEPTH
+    }
+
+    /// Mutate the program, rejecting offspring that exceed the size or depth
+    /// limits. If no acceptable mutation is found, the program is shrunk until
+    /// it fits instead.
     pub fn mutate(&mut self, rng: &mut ExprRng) {
+        for _ in 0..MAX_MUTATION_ATTEMPTS {
+            let mut candidate = self.clone();
+            candidate.mutate_unbounded(rng);
+            if candidate.is_within_limits() {
+                *self = candidate;
+                return;
+            }
+        }
+        self.repair(rng);
+    }
+
+    fn mutate_unbounded(&mut self, rng: &mut ExprRng) {
+        let roll: f64 = rng.gen_range(0.0..1.0);
+        let node = self.random_node(rng);
+        if roll < HOIST_MUTATION_RATE {
+            // Leaves have nothing to hoist
+            if !node.hoist_expr(rng) {
+                node.mutate_expr(rng);
+            }
+        } else if roll < HOIST_MUTATION_RATE + SHRINK_MUTATION_RATE {
+            node.shrink_expr(rng);
+        } else {
+            node.mutate_expr(rng);
+        }
+    }
+
+    /// Shrink random subtrees until the program is within the limits again.
+    pub fn repair(&mut self, rng: &mut ExprRng) {
+        for _ in 0..MAX_MUTATION_ATTEMPTS {
+            if self.is_within_limits() {
+                return;
+            }
+            self.random_node(rng).shrink_expr(rng);
+        }
+        if !self.is_within_limits() {
+            self.root.shrink_expr(rng);
+        }
+    }
+
+    fn random_node(&mut self, rng: &mut ExprRng) -> &mut dyn MutableExprSlot {
         let total_size = self.root.size();
         let index: u64 = rng.gen_range(0..total_size);
 
         let path_to_node = find_node(&mut self.root, index);
-        let node = get_node(&mut self.root, path_to_node.as_found());
-        node.mutate_expr(rng);
+        get_node(&mut self.root, path_to_node.into_found())
+    }
+
+    /// The program as an indented tree, one node per line, with the values
+    /// of constants, e.g.
+    /// ```text
+    /// Split
+    ///   impulse: FishDirection
+    ///     origin: GetSelf
+    ///     target: DichtsteVis
+    ///   mass_fraction: Const 0.20
+    /// ```
+    pub fn tree_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        push_tree_lines(&self.root, None, 0, &mut lines);
+        lines
+    }
+
+    /// A hash of the program's shape, ignoring the values of constants.
+    pub fn shape_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hash_shape(&self.root, &mut hasher);
+        hasher.finish()
     }
 
     pub fn mutated(&self, rng: &mut ExprRng) -> Self {
@@ -53,15 +166,56 @@ impl Program {
     }
 }
 
+fn push_tree_lines(
+    slot: &dyn MutableExprSlot,
+    slot_name: Option<&str>,
+    indent: usize,
+    lines: &mut Vec<String>,
+) {
+    let mut label = match slot_name {
+        Some(name) => format!("{}: {}", name, slot.node_name()),
+        None => slot.node_name().to_owned(),
+    };
+    if let Some(value) = slot.value_label() {
+        label = format!("{} {}", label, value);
+    }
+    lines.push(format!("{}{}", "  ".repeat(indent), label));
+    for i in 0..slot.num_children() {
+        push_tree_lines(
+            slot.borrow_nth_child(i),
+            Some(slot.child_name(i)),
+            indent + 1,
+            lines,
+        );
+    }
+}
+
+fn hash_shape(slot: &dyn MutableExprSlot, hasher: &mut DefaultHasher) {
+    slot.node_name().hash(hasher);
+    slot.num_children().hash(hasher);
+    for i in 0..slot.num_children() {
+        hash_shape(slot.borrow_nth_child(i), hasher);
+    }
+}
+
 pub struct InterpreterState<'a> {
     pub fish_num: usize,
     pub fishes: &'a Vec<Fish>,
+    pub food: &'a Food,
+    pub environment: &'a Environment,
+    pub obstacles: &'a [Obstacle],
+    /// Indices of the fishes the program looked at, for debugging.
+    pub sensed: RefCell<Vec<usize>>,
 }
 
 impl<'a> InterpreterState<'a> {
     pub fn get_self(&self) -> &'a Fish {
         &self.fishes[self.fish_num]
     }
+
+    pub fn sense(&self, fish_num: usize) {
+        self.sensed.borrow_mut().push(fish_num);
+    }
 }
 
 // -------------------------------------------------------------------------
@@ -76,6 +230,11 @@ pub trait Mutable<T> {
 /// Expression that evaluates to T
 pub trait Expr<T>: ExprClone<T> + Mutable<T> + ExprTreeNode {
     fn eval(&self, s: &InterpreterState) -> T;
+
+    /// The value of a constant, shown when printing programs.
+    fn value_label(&self) -> Option<String> {
+        None
+    }
 }
 
 // This split of from the main Expr trait mainly because we generate impl for this one
@@ -84,6 +243,12 @@ pub trait ExprTreeNode {
     fn borrow_nth_child(&self, n: u64) -> &dyn MutableExprSlot;
     fn borrow_nth_child_mut(&mut self, n: u64) -> &mut dyn MutableExprSlot;
     fn num_children(&self) -> u64;
+    /// Short human readable name of this kind of node
+    fn node_name(&self) -> &'static str;
+    /// Name of the slot the nth child lives in
+    fn child_name(&self, n: u64) -> &'static str;
+    /// e.g. `Split(impulse: Vec2, mass_fraction: Fraction) -> Action`
+    fn signature(&self) -> &'static str;
 }
 
 // https://stackoverflow.com/questions/30353462/how-to-clone-a-struct-storing-a-boxed-trait-object
@@ -144,9 +309,19 @@ impl<T> From<BoxedExpr<T>> for ExprSlot<T> {
 
 pub trait MutableExprSlot {
     fn mutate_expr(&mut self, rng: &mut ExprRng);
+    /// Replace this subtree by a copy of one of its descendants of the same
+    /// type. Returns false if there is no such descendant.
+    fn hoist_expr(&mut self, rng: &mut ExprRng) -> bool;
+    /// Replace this subtree by a freshly generated one of minimal depth.
+    fn shrink_expr(&mut self, rng: &mut ExprRng);
     fn borrow_nth_child_mut(&mut self, n: u64) -> &mut dyn MutableExprSlot;
     fn borrow_nth_child(&self, n: u64) -> &dyn MutableExprSlot;
     fn num_children(&self) -> u64;
+    fn node_name(&self) -> &'static str;
+    fn child_name(&self, n: u64) -> &'static str;
+    fn signature(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+    fn value_label(&self) -> Option<String>;
 
     fn size(&self) -> u64 {
         let children_size = (0..self.num_children())
@@ -154,13 +329,38 @@ pub trait MutableExprSlot {
             .sum::<u64>();
         children_size + 1
     }
+
+    fn depth(&self) -> u64 {
+        let children_depth = (0..self.num_children())
+            .map(|child_num| self.borrow_nth_child(child_num).depth())
+            .max()
+            .unwrap_or(0);
+        children_depth + 1
+    }
 }
 
-impl<T> MutableExprSlot for ExprSlot<T> {
+impl<T: 'static> MutableExprSlot for ExprSlot<T> {
     fn mutate_expr(&mut self, rng: &mut ExprRng) {
         self.inner = self.inner.mutate(rng)
     }
 
+    fn hoist_expr(&mut self, rng: &mut ExprRng) -> bool {
+        let mut candidates: Vec<&ExprSlot<T>> = Vec::new();
+        for i in 0..self.num_children() {
+            collect_descendants(self.borrow_nth_child(i), &mut candidates);
+        }
+        if candidates.is_empty() {
+            return false;
+        }
+        let hoisted = candidates[rng.gen_range(0..candidates.len())].clone();
+        *self = hoisted;
+        true
+    }
+
+    fn shrink_expr(&mut self, rng: &mut ExprRng) {
+        self.inner = generate_minimal_expr(rng);
+    }
+
     fn borrow_nth_child_mut(&mut self, n: u64) -> &mut dyn MutableExprSlot {
         self.inner.borrow_nth_child_mut(n)
     }
@@ -172,6 +372,38 @@ impl<T> MutableExprSlot for ExprSlot<T> {
     fn num_children(&self) -> u64 {
         self.inner.num_children()
     }
+
+    fn node_name(&self) -> &'static str {
+        self.inner.node_name()
+    }
+
+    fn child_name(&self, n: u64) -> &'static str {
+        self.inner.child_name(n)
+    }
+
+    fn signature(&self) -> &'static str {
+        self.inner.signature()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn value_label(&self) -> Option<String> {
+        self.inner.value_label()
+    }
+}
+
+fn collect_descendants<'a, T: 'static>(
+    slot: &'a dyn MutableExprSlot,
+    found: &mut Vec<&'a ExprSlot<T>>,
+) {
+    if let Some(slot) = slot.as_any().downcast_ref::<ExprSlot<T>>() {
+        found.push(slot);
+    }
+    for i in 0..slot.num_children() {
+        collect_descendants(slot.borrow_nth_child(i), found);
+    }
 }
 
 // -------------------------------------------------------------------------
@@ -182,7 +414,7 @@ enum FindNodeResult {
 }
 
 impl FindNodeResult {
-    fn as_found(self) -> Vec<u64> {
+    fn into_found(self) -> Vec<u64> {
         match self {
             FindNodeResult::NumVisited(_) => panic!("node not found"),
             FindNodeResult::FoundNode(path) => path,
@@ -190,20 +422,17 @@ impl FindNodeResult {
     }
 }
 
-fn get_node<'a>(
-    root: &'a mut dyn MutableExprSlot,
-    reverse_path: Vec<u64>,
-) -> &'a mut dyn MutableExprSlot {
+fn get_node(root: &mut dyn MutableExprSlot, reverse_path: Vec<u64>) -> &mut dyn MutableExprSlot {
     let mut pos = root;
 
     for &child_index in reverse_path.iter().rev() {
         pos = pos.borrow_nth_child_mut(child_index);
     }
 
-    return pos;
+    pos
 }
 
-fn find_node<'a>(root: &'a mut dyn MutableExprSlot, index: u64) -> FindNodeResult {
+fn find_node(root: &mut dyn MutableExprSlot, index: u64) -> FindNodeResult {
     use FindNodeResult::*;
 
     if index == 0 {
@@ -224,3 +453,97 @@ fn find_node<'a>(root: &'a mut dyn MutableExprSlot, index: u64) -> FindNodeResul
     }
     NumVisited(num_visited)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::lang::expressions::{ConstExpr, IfExpr};
+    use crate::vec2::Vec2;
+
+    fn if_pass(consequent: BoxedExpr<Action>) -> BoxedExpr<Action> {
+        Box::new(IfExpr {
+            condition: ExprSlot::new(Box::new(ConstExpr::new(true))),
+            consequent: ExprSlot::new(consequent),
+            alternative: ExprSlot::new(Box::new(ConstExpr::new(Action::Pass))),
+        })
+    }
+
+    #[test]
+    fn prints_constants() {
+        let program = Program {
+            root: ExprSlot::new(if_pass(Box::new(ConstExpr::new(Action::Pass)))),
+        };
+        assert_eq!(
+            program.tree_lines(),
+            vec![
+                "If",
+                "  condition: Const true",
+                "  consequent: Const Pass",
+                "  alternative: Const Pass",
+            ]
+        );
+    }
+
+    #[test]
+    fn shape_ignores_constants() {
+        let program = |action| Program {
+            root: ExprSlot::new(if_pass(Box::new(ConstExpr::new(action)))),
+        };
+        let pass = program(Action::Pass);
+        let split = program(Action::Move(Vec2::zero()));
+        assert_eq!(pass.shape_hash(), split.shape_hash());
+        assert_ne!(pass.shape_hash(), Program::empty().shape_hash());
+    }
+
+    #[test]
+    fn hoists_descendants_of_the_same_type() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        let mut program = Program {
+            root: ExprSlot::new(if_pass(Box::new(ConstExpr::new(Action::Pass)))),
+        };
+        assert!(program.root.hoist_expr(&mut rng));
+        assert_eq!(program.size(), 1);
+        // A lone constant has nothing left to hoist
+        assert!(!program.root.hoist_expr(&mut rng));
+    }
+
+    #[test]
+    fn shrinks_to_minimal_depth() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        let mut program = Program {
+            root: ExprSlot::new(grammar().generate_action(&mut rng, 10, GenerationMethod::Full)),
+        };
+        program.root.shrink_expr(&mut rng);
+        // The grammar counts leaves as depth 0, slots count them as 1
+        assert!(program.depth() <= grammar().min_depth(Symbol::Action) + 1);
+    }
+
+    #[test]
+    fn repairs_oversized_programs() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        let mut root: BoxedExpr<Action> = Box::new(ConstExpr::new(Action::Pass));
+        for _ in 0..MAX_PROGRAM_DEPTH {
+            root = if_pass(root);
+        }
+        let mut program = Program {
+            root: ExprSlot::new(root),
+        };
+        assert!(!program.is_within_limits());
+        program.repair(&mut rng);
+        assert!(program.is_within_limits());
+    }
+
+    #[test]
+    fn mutations_stay_within_limits() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        for mut program in Program::ramped_half_and_half(&mut rng, 4, 2, 8) {
+            for _ in 0..50 {
+                program.mutate(&mut rng);
+                assert!(program.depth() <= MAX_PROGRAM_DEPTH);
+                assert!(program.size() <= MAX_PROGRAM_SIZE);
+            }
+        }
+    }
+}
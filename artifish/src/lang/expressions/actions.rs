@@ -5,6 +5,7 @@ use crate::lang::generators::*;
 use crate::vec2::*;
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Action", mutate)]
 pub struct MoveExpr {
     pub direction: ExprSlot<Vec2>,
 }
@@ -16,48 +17,23 @@ impl Expr<Action> for MoveExpr {
     }
 }
 
-impl Mutable<Action> for MoveExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Action> {
-        branch_using!(rng, {
-            wrap_in_generic::<Action>(self, rng),
-            Box::new(MoveExpr {
-                direction: self.direction.mutate(rng),
-            })
-        })
-    }
-}
-
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Action", mutate)]
 pub struct SetVelocityExpr {
-    pub target_velocity: ExprSlot<Vec2>,
+    pub target_velocity: ExprSlot<Velocity>,
     pub max_energy_ratio: ExprSlot<Fraction>,
 }
 
 impl Expr<Action> for SetVelocityExpr {
     fn eval(&self, state: &InterpreterState) -> Action {
-        let velocity_vec = self.target_velocity.eval(state);
+        let Velocity(velocity_vec) = self.target_velocity.eval(state);
         let max_energy_ratio = self.max_energy_ratio.eval(state);
         Action::SetVelocity(velocity_vec, max_energy_ratio)
     }
 }
 
-impl Mutable<Action> for SetVelocityExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Action> {
-        branch_using!(rng, {
-            wrap_in_generic::<Action>(self, rng),
-            Box::new(SetVelocityExpr {
-                target_velocity: self.target_velocity.mutate(rng),
-                max_energy_ratio: self.max_energy_ratio.clone(),
-            }),
-            Box::new(SetVelocityExpr {
-                target_velocity: self.target_velocity.clone(),
-                max_energy_ratio: self.max_energy_ratio.mutate(rng),
-            })
-        })
-    }
-}
-
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Action", mutate)]
 pub struct SplitExpr {
     pub impulse: ExprSlot<Vec2>,
     pub mass_fraction: ExprSlot<Fraction>,
@@ -71,18 +47,19 @@ impl Expr<Action> for SplitExpr {
     }
 }
 
-impl Mutable<Action> for SplitExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Action> {
-        branch_using!(rng, {
-            wrap_in_generic::<Action>(self, rng),
-            Box::new(SplitExpr {
-                impulse: self.impulse.mutate(rng),
-                mass_fraction: self.mass_fraction.clone(),
-            }),
-            Box::new(SplitExpr {
-                impulse: self.impulse.clone(),
-                mass_fraction: self.mass_fraction.mutate(rng),
-            })
-        })
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Action", mutate)]
+pub struct BiteExpr {
+    pub target: ExprSlot<FishRef>,
+    pub size: ExprSlot<Fraction>,
+}
+
+impl Expr<Action> for BiteExpr {
+    fn eval(&self, state: &InterpreterState) -> Action {
+        let size = self.size.eval(state);
+        match self.target.eval(state).maybe_fish_num {
+            Some(target) => Action::Bite(target, size),
+            None => Action::Pass,
+        }
     }
 }
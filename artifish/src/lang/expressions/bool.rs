@@ -2,6 +2,7 @@ use crate::lang::core::*;
 use crate::lang::generators::*;
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "T")]
 pub struct NotExpr<T> {
     pub value: ExprSlot<T>,
 }
@@ -11,7 +12,7 @@ where
     T: std::ops::Not<Output = T> + Clone + 'static,
 {
     fn eval(&self, state: &InterpreterState) -> T {
-        return !self.value.eval(state);
+        !self.value.eval(state)
     }
 }
 
@@ -5,8 +5,11 @@ use crate::color::Color;
 use crate::fish::*;
 use crate::lang::core::*;
 use crate::lang::generators::*;
+use crate::lang::perturb::*;
+use crate::vec2::Velocity;
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "T")]
 pub struct IfExpr<T> {
     pub condition: ExprSlot<bool>,
     pub consequent: ExprSlot<T>,
@@ -55,6 +58,7 @@ where
 }
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "T")]
 pub struct ConstExpr<T> {
     #[expr_tree_node(not_a_child)]
     pub value: T,
@@ -62,28 +66,74 @@ pub struct ConstExpr<T> {
 
 impl<T> ConstExpr<T> {
     pub fn new(value: T) -> Self {
-        Self { value: value }
+        Self { value }
     }
 }
 
 impl<T> Expr<T> for ConstExpr<T>
 where
     Self: Mutable<T>,
-    T: Clone + 'static,
+    T: Clone + ValueLabel + 'static,
 {
     fn eval(&self, _: &InterpreterState) -> T {
-        return self.value.clone();
+        self.value.clone()
+    }
+
+    fn value_label(&self) -> Option<String> {
+        Some(self.value.label())
+    }
+}
+
+/// How constants are written when printing programs.
+pub trait ValueLabel {
+    fn label(&self) -> String;
+}
+
+impl ValueLabel for NotNan<f64> {
+    fn label(&self) -> String {
+        format!("{:.2}", self.into_inner())
+    }
+}
+
+impl ValueLabel for Fraction {
+    fn label(&self) -> String {
+        format!("{:.2}", self.to_f64())
+    }
+}
+
+impl ValueLabel for bool {
+    fn label(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ValueLabel for Action {
+    fn label(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl ValueLabel for Color {
+    fn label(&self) -> String {
+        let [r, g, b, a] = self.inner;
+        format!("({:.2}, {:.2}, {:.2}, {:.2})", r, g, b, a)
+    }
+}
+
+impl ValueLabel for Velocity {
+    fn label(&self) -> String {
+        format!("({:.2}, {:.2})", self.0.x, self.0.y)
     }
 }
 
 impl Mutable<NotNan<f64>> for ConstExpr<NotNan<f64>> {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<NotNan<f64>> {
+        if should_perturb(rng) {
+            return Box::new(ConstExpr::new(self.value.perturbed(rng)));
+        }
         branch_using!(rng, {
             wrap_in_generic(self, rng),
-            Box::new(NegateExpr {
-                value: ExprSlot { inner: Box::new(self.clone())},
-            }),
-            generate_f64_expr(rng, F64_MIN),
+            generate_minimal_expr(rng),
         })
     }
 }
@@ -92,7 +142,7 @@ impl Mutable<Action> for ConstExpr<Action> {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Action> {
         branch_using!(rng, {
             wrap_in_generic(self, rng),
-            generate_action_expr(rng, ACTION_MIN),
+            generate_minimal_expr(rng),
         })
     }
 }
@@ -101,25 +151,43 @@ impl Mutable<bool> for ConstExpr<bool> {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<bool> {
         branch_using!(rng, {
             wrap_in_generic(self, rng),
-            generate_bool_expr(rng, 0),
+            generate_minimal_expr(rng),
         })
     }
 }
 
 impl Mutable<Fraction> for ConstExpr<Fraction> {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Fraction> {
+        if should_perturb(rng) {
+            return Box::new(ConstExpr::new(self.value.perturbed(rng)));
+        }
         branch_using!(rng, {
             wrap_in_generic(self, rng),
-            generate_fraction_expr(rng, FRACTION_MIN),
+            generate_minimal_expr(rng),
         })
     }
 }
 
 impl Mutable<Color> for ConstExpr<Color> {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Color> {
+        if should_perturb(rng) {
+            return Box::new(ConstExpr::new(self.value.perturbed(rng)));
+        }
+        branch_using!(rng, {
+            wrap_in_generic(self, rng),
+            generate_minimal_expr(rng),
+        })
+    }
+}
+
+impl Mutable<Velocity> for ConstExpr<Velocity> {
+    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Velocity> {
+        if should_perturb(rng) {
+            return Box::new(ConstExpr::new(self.value.perturbed(rng)));
+        }
         branch_using!(rng, {
             wrap_in_generic(self, rng),
-            generate_color_expr(rng, COLOR_MIN),
+            generate_minimal_expr(rng),
         })
     }
 }
@@ -23,11 +23,12 @@ impl Fraction {
 impl From<Fraction> for N64 {
     fn from(f: Fraction) -> Self {
         let Fraction(inner) = f;
-        return inner;
+        inner
     }
 }
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "bool")]
 pub struct LessThenExpr<T> {
     pub left: ExprSlot<T>,
     pub right: ExprSlot<T>,
@@ -48,7 +49,7 @@ where
 {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<bool> {
         branch_using!(rng, {
-            generate_bool_expr(rng, 0),
+            generate_minimal_expr(rng),
             wrap_in_generic::<bool>(self, rng),
             Box::new(LessThenExpr {
                 left: self.left.clone(),
@@ -63,6 +64,7 @@ where
 }
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "T")]
 pub struct AddExpr<T> {
     pub left: ExprSlot<T>,
     pub right: ExprSlot<T>,
@@ -73,7 +75,7 @@ where
     T: std::ops::Add<Output = T> + Clone + 'static,
 {
     fn eval(&self, state: &InterpreterState) -> T {
-        return self.left.eval(state) + self.right.eval(state);
+        self.left.eval(state) + self.right.eval(state)
     }
 }
 
@@ -99,6 +101,12 @@ where
 }
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(
+    output = "<T1 as std::ops::Mul<T2>>::Output",
+    bound = "T1: std::ops::Mul<T2> + Clone + 'static, T2: Clone + 'static, \
+             <T1 as std::ops::Mul<T2>>::Output: Clone + 'static",
+    mutate
+)]
 pub struct MulExpr<T1, T2> {
     pub left: ExprSlot<T1>,
     pub right: ExprSlot<T2>,
@@ -111,34 +119,12 @@ where
     <T1 as std::ops::Mul<T2>>::Output: Clone + 'static,
 {
     fn eval(&self, state: &InterpreterState) -> <T1 as std::ops::Mul<T2>>::Output {
-        return self.left.eval(state) * self.right.eval(state);
-    }
-}
-
-impl<T1, T2> Mutable<<T1 as std::ops::Mul<T2>>::Output> for MulExpr<T1, T2>
-where
-    T1: std::ops::Mul<T2> + Clone + 'static,
-    T2: Clone + 'static,
-    <T1 as std::ops::Mul<T2>>::Output: Clone + 'static,
-{
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<<T1 as std::ops::Mul<T2>>::Output> {
-        branch_using!(rng, {
-            wrap_in_generic::<<T1 as std::ops::Mul<T2>>::Output>(self, rng),
-            // self.left.inner.clone(),
-            // self.right.inner.clone(),
-            Box::new(MulExpr {
-                left: self.left.clone(),
-                right: self.right.mutate(rng),
-            }),
-            Box::new(MulExpr {
-                left: self.left.mutate(rng),
-                right: self.right.clone(),
-            })
-        })
+        self.left.eval(state) * self.right.eval(state)
     }
 }
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "T")]
 pub struct NegateExpr<T> {
     pub value: ExprSlot<T>,
 }
@@ -148,7 +134,7 @@ where
     T: std::ops::Neg<Output = T> + Clone + 'static,
 {
     fn eval(&self, state: &InterpreterState) -> T {
-        return -self.value.eval(state);
+        -self.value.eval(state)
     }
 }
 
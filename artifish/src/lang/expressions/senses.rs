@@ -3,12 +3,16 @@ use decorum::NotNan;
 use crate::color::Color;
 use crate::lang::core::*;
 use crate::lang::generators::*;
+use crate::lang::grammar::{min_depth, Symbol};
 use crate::lang::Fraction;
+use crate::obstacles::line_of_sight;
 use crate::vec2::*;
+use crate::{MAX_X, MAX_Y};
 
 // region: color
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Color", regenerate)]
 pub struct FishColorExpr {
     pub fish: ExprSlot<FishRef>,
 }
@@ -24,16 +28,8 @@ impl Expr<Color> for FishColorExpr {
     }
 }
 
-impl Mutable<Color> for FishColorExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Color> {
-        branch_using!(rng, {
-            generate_color_expr(rng, COLOR_MIN),
-            wrap_in_generic(self, rng),
-        })
-    }
-}
-
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Fraction", regenerate)]
 pub struct ColorSimilarityExpr {
     pub lhs: ExprSlot<Color>,
     pub rhs: ExprSlot<Color>,
@@ -62,16 +58,7 @@ impl Expr<Fraction> for ColorSimilarityExpr {
         // catch rounding errors
         let cos = cos.clamp(0.0, 1.0);
 
-        return Fraction::from_f64(cos as f64);
-    }
-}
-
-impl Mutable<Fraction> for ColorSimilarityExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Fraction> {
-        branch_using!(rng, {
-            generate_fraction_expr(rng, FRACTION_MIN),
-            wrap_in_generic(self, rng),
-        })
+        Fraction::from_f64(cos as f64)
     }
 }
 
@@ -79,6 +66,7 @@ impl Mutable<Fraction> for ColorSimilarityExpr {
 
 // region: getself
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "FishRef", regenerate)]
 pub struct GetSelfExpr;
 
 impl Expr<FishRef> for GetSelfExpr {
@@ -89,20 +77,12 @@ impl Expr<FishRef> for GetSelfExpr {
     }
 }
 
-impl Mutable<FishRef> for GetSelfExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<FishRef> {
-        branch_using!(rng, {
-            generate_fish_ref_expr(rng, FISH_REF_MIN),
-            wrap_in_generic(self, rng),
-        })
-    }
-}
-
 // endregion: getself
 
 // region: dichtstevis
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "FishRef", regenerate)]
 pub struct DichtsteVisExpr;
 
 #[derive(Clone, Debug)]
@@ -112,33 +92,35 @@ pub struct FishRef {
 
 impl Expr<FishRef> for DichtsteVisExpr {
     fn eval(&self, state: &InterpreterState) -> FishRef {
+        let me = state.get_self();
+        let position = Vec2::new(me.x, me.y);
         let maybe_j = state
             .fishes
             .iter()
             .enumerate()
             .filter(|(j, _)| j != &state.fish_num)
-            .min_by_key(|(_, fish)| NotNan::from_inner(state.get_self().distance(fish)))
+            .map(|(j, fish)| (j, me.displacement_to(fish)))
+            // Fishes hiding behind obstacles can't be seen
+            .filter(|(_, displacement)| {
+                line_of_sight(state.obstacles, position, position + *displacement)
+            })
+            .min_by_key(|(_, displacement)| NotNan::from_inner(displacement.length()))
             .map(|(j, _)| j);
+        if let Some(j) = maybe_j {
+            state.sense(j);
+        }
         FishRef {
             maybe_fish_num: maybe_j,
         }
     }
 }
 
-impl Mutable<FishRef> for DichtsteVisExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<FishRef> {
-        branch_using!(rng, {
-            wrap_in_generic(self, rng),
-            generate_fish_ref_expr(rng, FISH_REF_MIN),
-        })
-    }
-}
-
 // endregion: dichtstevis
 
 // region: fishenergy
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "NotNan<f64>", regenerate)]
 pub struct FishEnergyExpr {
     pub fish: ExprSlot<FishRef>,
 }
@@ -155,18 +137,10 @@ impl Expr<NotNan<f64>> for FishEnergyExpr {
     }
 }
 
-impl Mutable<NotNan<f64>> for FishEnergyExpr {
-    fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<NotNan<f64>> {
-        branch_using!(rng, {
-            wrap_in_generic(self, rng),
-            generate_f64_expr(rng, F64_MIN),
-        })
-    }
-}
-
 // endregion: fishenergy
 
 #[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Vec2")]
 pub struct FishDirectionExpr {
     pub origin: ExprSlot<FishRef>,
     pub target: ExprSlot<FishRef>,
@@ -182,7 +156,10 @@ impl Expr<Vec2> for FishDirectionExpr {
         } = self.target.eval(state);
 
         match (origin, target) {
-            (Some(o), Some(t)) => state.fishes[o].direction_to(&state.fishes[t]),
+            (Some(o), Some(t)) => {
+                state.sense(t);
+                state.fishes[o].direction_to(&state.fishes[t])
+            }
             _ => Vec2::zero(),
         }
     }
@@ -192,7 +169,7 @@ impl Mutable<Vec2> for FishDirectionExpr {
     fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<Vec2> {
         branch_using!(rng, {
             wrap_in_generic(self, rng),
-            generate_direction_expr(rng, DIRECTION_MIN),
+            generate_direction_expr(rng, min_depth(Symbol::Direction)),
             Box::new(FishDirectionExpr {
                 origin: self.target.clone(),
                 target: self.origin.clone(),
@@ -200,3 +177,184 @@ impl Mutable<Vec2> for FishDirectionExpr {
         })
     }
 }
+
+// region: food
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Vec2", regenerate)]
+pub struct FoodDirectionExpr;
+
+impl Expr<Vec2> for FoodDirectionExpr {
+    fn eval(&self, state: &InterpreterState) -> Vec2 {
+        match state.food.nearest(state.get_self()) {
+            Some((_, displacement)) if displacement.length() > 0.0 => displacement.normalized(),
+            _ => Vec2::zero(),
+        }
+    }
+}
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "NotNan<f64>", regenerate)]
+pub struct FoodDistanceExpr;
+
+impl Expr<NotNan<f64>> for FoodDistanceExpr {
+    fn eval(&self, state: &InterpreterState) -> NotNan<f64> {
+        // Without any food around, pretend it is as far away as can be
+        let distance = match state.food.nearest(state.get_self()) {
+            Some((_, displacement)) => displacement.length(),
+            None => MAX_X.hypot(MAX_Y),
+        };
+        NotNan::from_inner(distance)
+    }
+}
+
+// endregion: food
+
+// region: environment
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Fraction", regenerate)]
+pub struct LightExpr;
+
+impl Expr<Fraction> for LightExpr {
+    fn eval(&self, state: &InterpreterState) -> Fraction {
+        let fish = state.get_self();
+        Fraction::from_f64(state.environment.light(fish.x, fish.y))
+    }
+}
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Fraction", regenerate)]
+pub struct TemperatureExpr;
+
+impl Expr<Fraction> for TemperatureExpr {
+    fn eval(&self, state: &InterpreterState) -> Fraction {
+        let fish = state.get_self();
+        Fraction::from_f64(state.environment.temperature(fish.x, fish.y))
+    }
+}
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Fraction", regenerate)]
+pub struct NutrientsExpr;
+
+impl Expr<Fraction> for NutrientsExpr {
+    fn eval(&self, state: &InterpreterState) -> Fraction {
+        let fish = state.get_self();
+        Fraction::from_f64(state.environment.nutrients(fish.x, fish.y))
+    }
+}
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "Velocity", regenerate)]
+pub struct CurrentExpr;
+
+impl Expr<Velocity> for CurrentExpr {
+    fn eval(&self, state: &InterpreterState) -> Velocity {
+        let fish = state.get_self();
+        Velocity(state.environment.current(fish.x, fish.y))
+    }
+}
+
+// endregion: environment
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::environment::{Current, Environment, Layer, Map};
+    use crate::fish::Fish;
+    use crate::food::{Food, FoodParticle};
+    use crate::lang::core::Program;
+
+    fn fish_at(x: f64, y: f64) -> Fish {
+        Fish::new(0, x, y, NotNan::from_inner(100.0), Program::empty())
+    }
+
+    fn particle(x: f64, y: f64) -> FoodParticle {
+        FoodParticle {
+            x,
+            y,
+            energy: NotNan::from_inner(1.0),
+        }
+    }
+
+    /// Evaluate as the first of `fishes`.
+    fn eval<T>(
+        expr: &dyn Expr<T>,
+        fishes: &Vec<Fish>,
+        food: &Food,
+        environment: &Environment,
+    ) -> T {
+        let state = InterpreterState {
+            fish_num: 0,
+            fishes,
+            food,
+            environment,
+            obstacles: &[],
+            sensed: RefCell::new(Vec::new()),
+        };
+        expr.eval(&state)
+    }
+
+    #[test]
+    fn senses_nearest_food() {
+        let fishes = vec![fish_at(100.0, 100.0)];
+        let environment = Environment::default();
+        let mut food = Food::new();
+        let direction = eval(&FoodDirectionExpr, &fishes, &food, &environment);
+        assert_eq!(direction.length(), 0.0);
+        assert_eq!(
+            eval(&FoodDistanceExpr, &fishes, &food, &environment).into_inner(),
+            MAX_X.hypot(MAX_Y)
+        );
+
+        food.particles.push(particle(100.0, 160.0));
+        food.particles.push(particle(70.0, 140.0));
+        let direction = eval(&FoodDirectionExpr, &fishes, &food, &environment);
+        assert!((direction - Vec2::new(-0.6, 0.8)).length() < 1e-9);
+        let distance = eval(&FoodDistanceExpr, &fishes, &food, &environment);
+        assert!((distance.into_inner() - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn senses_the_water_where_it_is() {
+        let layer = |map| Layer { map, cycle: None };
+        let environment = Environment {
+            light: layer(Map::Gradient {
+                direction: Vec2::new(0.0, 1.0),
+                start: 1.0,
+                end: 0.2,
+            }),
+            temperature: layer(Map::Constant(0.25)),
+            nutrients: layer(Map::Grid {
+                columns: 2,
+                rows: 2,
+                values: vec![0.0, 1.0, 0.0, 1.0],
+            }),
+            currents: Vec::new(),
+            time: 0.0,
+        };
+        let fishes = vec![fish_at(MAX_X / 4.0, MAX_Y / 2.0)];
+        let food = Food::new();
+        let sense = |expr: &dyn Expr<Fraction>| eval(expr, &fishes, &food, &environment).to_f64();
+        assert!((sense(&LightExpr) - 0.6).abs() < 1e-9);
+        assert!((sense(&TemperatureExpr) - 0.25).abs() < 1e-9);
+        assert!((sense(&NutrientsExpr) - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn senses_the_current() {
+        let environment = Environment {
+            currents: vec![
+                Current::Uniform(Vec2::new(1.0, 2.0)),
+                Current::Uniform(Vec2::new(0.5, -1.0)),
+            ],
+            ..Environment::default()
+        };
+        let fishes = vec![fish_at(100.0, 100.0)];
+        let Velocity(current) = eval(&CurrentExpr, &fishes, &Food::new(), &environment);
+        assert!((current - Vec2::new(1.5, 1.0)).length() < 1e-9);
+    }
+}
@@ -1,247 +1,107 @@
+use std::any::Any;
+
 use decorum::NotNan;
-use rand::Rng;
 
 use super::core::*;
 use super::expressions::*;
+use super::grammar::{grammar, min_depth, symbol_of, GenerationMethod, Production, Symbol};
 
 use crate::color::Color;
 use crate::fish::Action;
 use crate::vec2::*;
 
-#[macro_export]
-macro_rules! generate_tree {
-    ( $depth:expr, $rng: ident,
-        0 => { $( $leaf:expr ),* $(,)? },
-        1 => { $( $depth1:expr ),* $(,)? },
-        2 => { $( $depth2:expr ),* $(,)? }$(,)?
-    ) => {
-        match $depth {
-            0 => {
-                branch_using!($rng, {
-                    $( $leaf, )*
-                })
-            },
-            1 => {
-                branch_using!($rng, {
-                    $( $leaf, )*
-                    $( $depth1, )*
-                })
-            },
-            _ => {
-                branch_using!($rng, {
-                    $( $leaf, )*
-                    $( $depth1, )*
-                    $( $depth2, )*
-                })
-            }
-        }
-    };
-    ( $depth:expr, $rng: ident,
-        { $( $leaf:expr ),* $(,)? },
-        { $( $recursive:expr ),* $(,)? }
-    ) => {
-       if $depth == 0 {
-           branch_using!($rng, {
-               $( $leaf, )*
-           })
-       } else {
-           branch_using!($rng, {
-               $( $leaf, )*
-               $( $recursive, )*
-           })
-       }
-   }
-
-}
-
-const fn min(a: u64, b: u64) -> u64 {
-    [a, b][(a > b) as usize]
-}
-
-const fn max(a: u64, b: u64) -> u64 {
-    [a, b][(a < b) as usize]
-}
-
-// -------------------------------------------------------------------------
-
-pub const ACTION_MIN: u64 = max(MOVE_MIN, max(SPLIT_MIN, VELOCITY_MIN)) + 1;
-pub fn generate_action_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Action> {
-    assert!(max_depth >= ACTION_MIN);
-    generate_tree!(max_depth - ACTION_MIN, rng, {
-        generate_move_expr(rng, max_depth),
-        generate_split_expr(rng, max_depth),
-        generate_set_velocity_expr(rng, max_depth),
-    }, {
-        generate_if_expr(generate_action_expr, rng, max_depth),
-    })
-}
-
-pub const MOVE_MIN: u64 = DIRECTION_MIN + 1;
-pub fn generate_move_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Action> {
-    assert!(max_depth >= MOVE_MIN);
-    generate_tree!(max_depth - MOVE_MIN, rng, {
-        Box::new(MoveExpr {
-            direction: ExprSlot::new(generate_direction_expr(rng, max_depth - 1)),
-        }),
-    }, {
-        generate_if_expr(generate_move_expr, rng, max_depth)
-    })
-}
+// Random trees are grown from the global grammar, see `grammar.rs`.
 
-pub const SET_VELOCITY_MIN: u64 = VELOCITY_MIN + 1;
-pub fn generate_set_velocity_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Action> {
-    assert!(max_depth >= SET_VELOCITY_MIN);
-    generate_tree!(max_depth - SET_VELOCITY_MIN, rng, {
-        Box::new(SetVelocityExpr {
-            target_velocity: ExprSlot::new(generate_velocity_expr(rng, max_depth - 1)),
-            max_energy_ratio: ExprSlot::new(generate_fraction_expr(rng, max_depth - 1)),
-        }),
-    }, {
-        generate_if_expr(generate_set_velocity_expr, rng, max_depth)
-    })
+pub fn generate_action_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Action> {
+    grammar().generate_action(rng, max_depth, GenerationMethod::Grow)
 }
 
-pub const SPLIT_MIN: u64 = max(DIRECTION_MIN, FRACTION_MIN) + 1;
-pub fn generate_split_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Action> {
-    assert!(max_depth >= SPLIT_MIN);
-    generate_tree!(max_depth - SPLIT_MIN, rng, {
-        Box::new(SplitExpr {
-            impulse: ExprSlot::new(generate_direction_expr(rng, max_depth - 1)),
-            mass_fraction: ExprSlot::new(generate_fraction_expr(rng, max_depth - 1)),
-        }),
-    }, {
-        generate_if_expr(generate_split_expr, rng, max_depth)
-    })
+pub fn generate_direction_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Vec2> {
+    grammar().generate_direction(rng, max_depth, GenerationMethod::Grow)
 }
 
-// TODO: this is not what we want longterm
-pub const DIRECTION_MIN: u64 = FISH_REF_MIN + 1;
-pub fn generate_direction_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Vec2> {
-    assert!(max_depth >= DIRECTION_MIN);
-    generate_tree!(max_depth - DIRECTION_MIN, rng, {
-        Box::new(FishDirectionExpr {
-            origin: ExprSlot::new(generate_fish_ref_expr(rng, max_depth - 1)),
-            target: ExprSlot::new(generate_fish_ref_expr(rng, max_depth - 1)),
-        }),
-     }, {
-        generate_if_expr(generate_direction_expr, rng, max_depth)
-    })
+pub fn generate_velocity_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Velocity> {
+    grammar().generate_velocity(rng, max_depth, GenerationMethod::Grow)
 }
 
-pub const VELOCITY_MIN: u64 = DIRECTION_MIN + 1;
-pub fn generate_velocity_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Vec2> {
-    assert!(max_depth >= VELOCITY_MIN);
-    generate_tree!(max_depth - VELOCITY_MIN, rng, {
-        Box::new(MulExpr {
-            left: ExprSlot::new(generate_direction_expr(rng, max_depth - 1)),
-            right: ExprSlot::new(generate_fraction_expr(rng, max_depth - 1)),
-        }),
-     }, {
-        generate_if_expr(generate_velocity_expr, rng, max_depth)
-    })
+pub fn generate_fish_ref_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<FishRef> {
+    grammar().generate_fish_ref(rng, max_depth, GenerationMethod::Grow)
 }
 
-pub const FISH_REF_MIN: u64 = 0;
-pub fn generate_fish_ref_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<FishRef> {
-    generate_tree!(max_depth, rng, {
-        Box::new(GetSelfExpr),
-        Box::new(DichtsteVisExpr),
-    }, {
-        generate_if_expr(generate_fish_ref_expr, rng, max_depth),
-    })
+pub fn generate_bool_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<bool> {
+    grammar().generate_bool(rng, max_depth, GenerationMethod::Grow)
 }
 
-pub fn generate_if_expr<F, T>(generator: F, rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<T>
-where
-    T: Clone + 'static,
-    F: Fn(&mut ExprRng, u64) -> BoxedExpr<T>,
-{
-    assert!(max_depth >= 1);
-    Box::new(IfExpr {
-        condition: generate_bool_expr(rng, max_depth - 1).into(),
-        consequent: generator(rng, max_depth - 1).into(),
-        alternative: generator(rng, max_depth - 1).into(),
-    })
+pub fn generate_f64_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<NotNan<f64>> {
+    grammar().generate_f64(rng, max_depth, GenerationMethod::Grow)
 }
 
-pub fn generate_bool_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<bool> {
-    generate_tree!(max_depth, rng,
-    0 => {
-        Box::new(ConstExpr::new(true)),
-        Box::new(ConstExpr::new(false))
-    }, 
-    1 => {
-        generate_if_expr(generate_bool_expr, rng, max_depth)
-    },
-    2 => {
-        Box::new(LessThenExpr {
-            left: generate_f64_expr(rng, max_depth - 1).into(),
-            right: generate_f64_expr(rng, max_depth - 1).into(),
-        }),
-    },)
+pub fn generate_fraction_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Fraction> {
+    grammar().generate_fraction(rng, max_depth, GenerationMethod::Grow)
 }
 
-pub const F64_MIN: u64 = 1;
-pub fn generate_f64_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<NotNan<f64>> {
-    assert!(max_depth > 0);
-    generate_tree!(max_depth - F64_MIN, rng, {
-        // TODO: Generate random f64
-        Box::new(FishEnergyExpr {
-            fish: ExprSlot::new(Box::new(GetSelfExpr)),
-        }),
-        Box::new(FishEnergyExpr {
-            fish: ExprSlot::new(Box::new(DichtsteVisExpr)),
-        })
-    },
-    {
-        generate_if_expr(generate_f64_expr, rng, max_depth)
-    })
-}
-
-pub const FRACTION_MIN: u64 = 1;
-pub fn generate_fraction_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Fraction> {
-    assert!(max_depth > 0);
-    generate_tree!(max_depth - FRACTION_MIN, rng, {
-        Box::new(ConstExpr::new(Fraction::from_f64(rng.gen_range(0.0..=1.0)))),
-    },
-    {
-        generate_if_expr(generate_fraction_expr, rng, max_depth)
-    })
-}
-
-pub const COLOR_MIN: u64 = 1;
-pub fn generate_color_expr(mut rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Color> {
-    assert!(max_depth > 0);
-    generate_tree!(max_depth - COLOR_MIN, rng, {
-        {
-            let color: [f32; 4] = [
-                rng.gen_range(0.0..=1.0),
-                rng.gen_range(0.0..=1.0),
-                rng.gen_range(0.0..=1.0),
-                1.0,
-                ];
-            Box::new(ConstExpr::new(Color::new(color)))
-        },
-    },
-    {
-        generate_if_expr(generate_color_expr, rng, max_depth)
-    })
+pub fn generate_color_expr(rng: &mut ExprRng, max_depth: u64) -> BoxedExpr<Color> {
+    grammar().generate_color(rng, max_depth, GenerationMethod::Grow)
 }
 
+/// Wrap an expression in a new node, picked with the odds the grammar gives
+/// the productions that take an expression of the same type. Grammars without
+/// any get a fresh minimal expression instead.
 pub fn wrap_in_generic<T: Clone + 'static>(
     expr: &dyn Expr<T>,
     mut rng: &mut ExprRng,
 ) -> BoxedExpr<T> {
-    branch_using!(rng, {
-        Box::new(IfExpr {
-            condition: generate_bool_expr(rng, 1).into(),
-            consequent: expr.clone_box().into(),
-            alternative: expr.mutate(rng).into(),
-        }),
-        Box::new(IfExpr {
-            condition: generate_bool_expr(rng, 1).into(),
-            consequent: expr.mutate(rng).into(),
-            alternative: expr.clone_box().into(),
-        })
-    })
+    match grammar().choose_wrapper(symbol_of::<T>(), rng) {
+        Some(Production::If) => {
+            let condition_depth = min_depth(Symbol::Bool).max(1);
+            branch_using!(rng, {
+                Box::new(IfExpr {
+                    condition: generate_bool_expr(rng, condition_depth).into(),
+                    consequent: expr.clone_box().into(),
+                    alternative: expr.mutate(rng).into(),
+                }),
+                Box::new(IfExpr {
+                    condition: generate_bool_expr(rng, condition_depth).into(),
+                    consequent: expr.mutate(rng).into(),
+                    alternative: expr.clone_box().into(),
+                })
+            })
+        }
+        Some(Production::Not) => cast(Box::new(NotExpr::<bool> {
+            value: cast(expr.clone_box()).into(),
+        })),
+        Some(Production::Negate) => cast(Box::new(NegateExpr::<NotNan<f64>> {
+            value: cast(expr.clone_box()).into(),
+        })),
+        Some(Production::Add) => cast(Box::new(AddExpr::<NotNan<f64>> {
+            left: cast(expr.clone_box()).into(),
+            right: generate_f64_expr(rng, min_depth(Symbol::F64)).into(),
+        })),
+        Some(production) => unreachable!("{:?} doesn't wrap expressions", production),
+        None => generate_minimal_expr(rng),
+    }
+}
+
+/// Convert between expression types that are known to be the same at
+/// runtime.
+fn cast<A: 'static, B: 'static>(expr: BoxedExpr<A>) -> BoxedExpr<B> {
+    let expr: Box<dyn Any> = Box::new(expr);
+    *expr.downcast::<BoxedExpr<B>>().unwrap()
+}
+
+/// Generate an expression of minimal depth for whichever type `T` is. This is
+/// used by shrink mutations, which only know the type of the slot at runtime.
+pub fn generate_minimal_expr<T: 'static>(rng: &mut ExprRng) -> BoxedExpr<T> {
+    let symbol = symbol_of::<T>();
+    let depth = min_depth(symbol);
+    match symbol {
+        Symbol::Action => cast(generate_action_expr(rng, depth)),
+        Symbol::Direction => cast(generate_direction_expr(rng, depth)),
+        Symbol::Velocity => cast(generate_velocity_expr(rng, depth)),
+        Symbol::FishRef => cast(generate_fish_ref_expr(rng, depth)),
+        Symbol::Bool => cast(generate_bool_expr(rng, depth)),
+        Symbol::F64 => cast(generate_f64_expr(rng, depth)),
+        Symbol::Fraction => cast(generate_fraction_expr(rng, depth)),
+        Symbol::Color => cast(generate_color_expr(rng, depth)),
+    }
 }
@@ -0,0 +1,825 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use decorum::NotNan;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use super::core::*;
+use super::expressions::*;
+use crate::color::Color;
+use crate::fish::{Action, PredationMode};
+use crate::vec2::{Vec2, Velocity};
+use crate::{MAX_CONST_SPEED, PREDATION_MODE};
+
+/// The nonterminals of the program grammar, one per value type. Unit
+/// directions are `Vec2`s and (bounded) velocities `Velocity`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Symbol {
+    Action,
+    Direction,
+    Velocity,
+    FishRef,
+    Bool,
+    F64,
+    Fraction,
+    Color,
+}
+
+impl Symbol {
+    pub const ALL: [Symbol; 8] = [
+        Symbol::Action,
+        Symbol::Direction,
+        Symbol::Velocity,
+        Symbol::FishRef,
+        Symbol::Bool,
+        Symbol::F64,
+        Symbol::Fraction,
+        Symbol::Color,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Symbol::Action => "action",
+            Symbol::Direction => "direction",
+            Symbol::Velocity => "velocity",
+            Symbol::FishRef => "fish_ref",
+            Symbol::Bool => "bool",
+            Symbol::F64 => "f64",
+            Symbol::Fraction => "fraction",
+            Symbol::Color => "color",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Symbol> {
+        Symbol::ALL.iter().copied().find(|s| s.name() == name)
+    }
+}
+
+/// Every way of building an expression the grammar knows about. Which ones
+/// are actually used, and how often, is up to the `Grammar`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Production {
+    If,
+    Move,
+    SetVelocity,
+    Split,
+    Pass,
+    FishDirection,
+    ScaledDirection,
+    ConstVelocity,
+    GetSelf,
+    Nearest,
+    ConstBool,
+    LessThan,
+    Not,
+    FishEnergy,
+    ConstF64,
+    Negate,
+    Add,
+    ConstFraction,
+    ColorSimilarity,
+    ConstColor,
+    FishColor,
+    FoodDirection,
+    FoodDistance,
+    Light,
+    Temperature,
+    Nutrients,
+    Current,
+    Bite,
+}
+
+impl Production {
+    pub const ALL: [Production; 28] = [
+        Production::If,
+        Production::Move,
+        Production::SetVelocity,
+        Production::Split,
+        Production::Pass,
+        Production::FishDirection,
+        Production::ScaledDirection,
+        Production::ConstVelocity,
+        Production::GetSelf,
+        Production::Nearest,
+        Production::ConstBool,
+        Production::LessThan,
+        Production::Not,
+        Production::FishEnergy,
+        Production::ConstF64,
+        Production::Negate,
+        Production::Add,
+        Production::ConstFraction,
+        Production::ColorSimilarity,
+        Production::ConstColor,
+        Production::FishColor,
+        Production::FoodDirection,
+        Production::FoodDistance,
+        Production::Light,
+        Production::Temperature,
+        Production::Nutrients,
+        Production::Current,
+        Production::Bite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        use Production::*;
+        match self {
+            If => "if",
+            Move => "move",
+            SetVelocity => "set_velocity",
+            Split => "split",
+            Pass => "pass",
+            FishDirection => "fish_direction",
+            ScaledDirection => "scaled_direction",
+            ConstVelocity => "const",
+            GetSelf => "self",
+            Nearest => "nearest",
+            ConstBool => "const",
+            LessThan => "less_than",
+            Not => "not",
+            FishEnergy => "energy",
+            ConstF64 => "const",
+            Negate => "negate",
+            Add => "add",
+            ConstFraction => "const",
+            ColorSimilarity => "color_similarity",
+            ConstColor => "const",
+            FishColor => "fish_color",
+            FoodDirection => "food_direction",
+            FoodDistance => "food_distance",
+            Light => "light",
+            Temperature => "temperature",
+            Nutrients => "nutrients",
+            Current => "current",
+            Bite => "bite",
+        }
+    }
+
+    /// The symbols of the children this production expands into when used
+    /// for `symbol`, or None if it can't produce that symbol at all.
+    pub fn children(self, symbol: Symbol) -> Option<Vec<Symbol>> {
+        use Production::*;
+        use Symbol as S;
+        let children = match (symbol, self) {
+            (_, If) => vec![S::Bool, symbol, symbol],
+            (S::Action, Move) => vec![S::Direction],
+            (S::Action, SetVelocity) => vec![S::Velocity, S::Fraction],
+            (S::Action, Split) => vec![S::Direction, S::Fraction],
+            (S::Action, Pass) => vec![],
+            (S::Direction, FishDirection) => vec![S::FishRef, S::FishRef],
+            (S::Velocity, ScaledDirection) => vec![S::Direction, S::Fraction],
+            (S::Velocity, ConstVelocity) => vec![],
+            (S::FishRef, GetSelf) => vec![],
+            (S::FishRef, Nearest) => vec![],
+            (S::Bool, ConstBool) => vec![],
+            (S::Bool, LessThan) => vec![S::F64, S::F64],
+            (S::Bool, Not) => vec![S::Bool],
+            (S::F64, FishEnergy) => vec![S::FishRef],
+            (S::F64, ConstF64) => vec![],
+            (S::F64, Negate) => vec![S::F64],
+            (S::F64, Add) => vec![S::F64, S::F64],
+            (S::Fraction, ConstFraction) => vec![],
+            (S::Fraction, ColorSimilarity) => vec![S::Color, S::Color],
+            (S::Color, ConstColor) => vec![],
+            (S::Color, FishColor) => vec![S::FishRef],
+            (S::Direction, FoodDirection) => vec![],
+            (S::F64, FoodDistance) => vec![],
+            (S::Fraction, Light) => vec![],
+            (S::Fraction, Temperature) => vec![],
+            (S::Fraction, Nutrients) => vec![],
+            (S::Velocity, Current) => vec![],
+            (S::Action, Bite) => vec![S::FishRef, S::Fraction],
+            _ => return None,
+        };
+        Some(children)
+    }
+
+    pub fn from_name(symbol: Symbol, name: &str) -> Option<Production> {
+        Production::ALL
+            .iter()
+            .copied()
+            .find(|p| p.name() == name && p.children(symbol).is_some())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenerationMethod {
+    /// Pick any production that fits in the remaining depth.
+    Grow,
+    /// Prefer productions with children, so branches reach the full depth.
+    Full,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub production: Production,
+    pub weight: f64,
+}
+
+#[derive(Debug)]
+pub enum GrammarError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+    Unproductive(Symbol),
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrammarError::Io(err) => write!(f, "could not read grammar: {}", err),
+            GrammarError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            GrammarError::Unproductive(symbol) => write!(
+                f,
+                "symbol {} has no production that terminates",
+                symbol.name()
+            ),
+        }
+    }
+}
+
+/// Weighted production rules per symbol, from which random programs are
+/// generated and mutated.
+///
+/// Grammars are either built with `with_rule` or parsed from a text file with
+/// one `symbol production weight` triple per line, e.g.
+/// ```text
+/// # symbol  production      weight
+/// action    set_velocity    2
+/// action    if              0.5
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grammar {
+    rules: HashMap<Symbol, Vec<Rule>>,
+    min_depths: HashMap<Symbol, u64>,
+}
+
+static GRAMMAR: OnceLock<Grammar> = OnceLock::new();
+
+/// The grammar used throughout the program. Mutations have no way of getting
+/// at a grammar other than this one, so it is global and set once at startup.
+pub fn grammar() -> &'static Grammar {
+    GRAMMAR.get_or_init(|| for_predation_mode(Grammar::default()))
+}
+
+/// Install the global grammar. Fails if it was already set or used.
+pub fn set_grammar(grammar: Grammar) -> Result<(), Grammar> {
+    GRAMMAR.set(for_predation_mode(grammar))
+}
+
+/// Bites do nothing when fish can only swallow each other whole, so programs
+/// shouldn't grow them.
+fn for_predation_mode(grammar: Grammar) -> Grammar {
+    if PREDATION_MODE != PredationMode::Cover {
+        return grammar;
+    }
+    grammar
+        .with_weight(Symbol::Action, Production::Bite, 0.0)
+        .build()
+        .expect("grammar has actions other than bite")
+}
+
+pub fn min_depth(symbol: Symbol) -> u64 {
+    grammar().min_depth(symbol)
+}
+
+/// The symbol that generates values of type `T`. Vectors are taken to be
+/// directions, as mutations can't tell them apart from velocities.
+pub fn symbol_of<T: 'static>() -> Symbol {
+    let type_id = TypeId::of::<T>();
+    if type_id == TypeId::of::<Action>() {
+        Symbol::Action
+    } else if type_id == TypeId::of::<Vec2>() {
+        Symbol::Direction
+    } else if type_id == TypeId::of::<Velocity>() {
+        Symbol::Velocity
+    } else if type_id == TypeId::of::<FishRef>() {
+        Symbol::FishRef
+    } else if type_id == TypeId::of::<bool>() {
+        Symbol::Bool
+    } else if type_id == TypeId::of::<NotNan<f64>>() {
+        Symbol::F64
+    } else if type_id == TypeId::of::<Fraction>() {
+        Symbol::Fraction
+    } else if type_id == TypeId::of::<Color>() {
+        Symbol::Color
+    } else {
+        panic!("no symbol for {}", std::any::type_name::<T>())
+    }
+}
+
+impl Default for Grammar {
+    fn default() -> Self {
+        use Production::*;
+        use Symbol as S;
+        Grammar::empty()
+            .with_rule(S::Action, Move, 1.0)
+            .with_rule(S::Action, SetVelocity, 1.0)
+            .with_rule(S::Action, Split, 1.0)
+            .with_rule(S::Action, Bite, 1.0)
+            .with_rule(S::Action, If, 1.0)
+            .with_rule(S::Direction, FishDirection, 1.0)
+            .with_rule(S::Direction, FoodDirection, 1.0)
+            .with_rule(S::Direction, If, 1.0)
+            .with_rule(S::Velocity, ScaledDirection, 1.0)
+            .with_rule(S::Velocity, ConstVelocity, 1.0)
+            .with_rule(S::Velocity, Current, 0.5)
+            .with_rule(S::Velocity, If, 1.0)
+            .with_rule(S::FishRef, GetSelf, 1.0)
+            .with_rule(S::FishRef, Nearest, 1.0)
+            .with_rule(S::FishRef, If, 1.0)
+            .with_rule(S::Bool, ConstBool, 2.0)
+            .with_rule(S::Bool, If, 1.0)
+            .with_rule(S::Bool, LessThan, 1.0)
+            .with_rule(S::F64, FishEnergy, 2.0)
+            .with_rule(S::F64, ConstF64, 1.0)
+            .with_rule(S::F64, FoodDistance, 1.0)
+            .with_rule(S::F64, If, 1.0)
+            .with_rule(S::Fraction, ConstFraction, 1.0)
+            .with_rule(S::Fraction, Light, 0.5)
+            .with_rule(S::Fraction, Temperature, 0.5)
+            .with_rule(S::Fraction, Nutrients, 0.5)
+            .with_rule(S::Fraction, If, 1.0)
+            .with_rule(S::Color, ConstColor, 1.0)
+            .with_rule(S::Color, If, 1.0)
+            .build()
+            .expect("default grammar is valid")
+    }
+}
+
+impl Grammar {
+    pub fn empty() -> Self {
+        Grammar {
+            rules: HashMap::new(),
+            min_depths: HashMap::new(),
+        }
+    }
+
+    /// Add a production for a symbol. Panics if the production can't produce
+    /// that symbol. Call `build` when done adding rules.
+    pub fn with_rule(mut self, symbol: Symbol, production: Production, weight: f64) -> Self {
+        assert!(production.children(symbol).is_some());
+        assert!(weight >= 0.0);
+        self.rules
+            .entry(symbol)
+            .or_default()
+            .push(Rule { production, weight });
+        self
+    }
+
+    /// Change the weight of every rule for a production of a symbol. Call
+    /// `build` again when done.
+    pub fn with_weight(mut self, symbol: Symbol, production: Production, weight: f64) -> Self {
+        assert!(weight >= 0.0);
+        for rule in self.rules.entry(symbol).or_default().iter_mut() {
+            if rule.production == production {
+                rule.weight = weight;
+            }
+        }
+        self
+    }
+
+    /// Check that every symbol can be generated, and compute minimal depths.
+    pub fn build(mut self) -> Result<Self, GrammarError> {
+        self.min_depths = HashMap::new();
+        loop {
+            let mut changed = false;
+            for &symbol in Symbol::ALL.iter() {
+                let depth = self
+                    .enabled_rules(symbol)
+                    .filter_map(|rule| self.production_min_depth(symbol, rule.production))
+                    .min();
+                if let Some(depth) = depth {
+                    if self.min_depths.get(&symbol).is_none_or(|&d| depth < d) {
+                        self.min_depths.insert(symbol, depth);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        for &symbol in Symbol::ALL.iter() {
+            if !self.min_depths.contains_key(&symbol) {
+                return Err(GrammarError::Unproductive(symbol));
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, GrammarError> {
+        let mut grammar = Grammar::empty();
+        for (i, line) in text.lines().enumerate() {
+            let line_num = i + 1;
+            let content = line.split('#').next().unwrap_or("").trim();
+            if content.is_empty() {
+                continue;
+            }
+            let parse_error = |message: String| GrammarError::Parse {
+                line: line_num,
+                message,
+            };
+
+            let parts: Vec<&str> = content.split_whitespace().collect();
+            if parts.len() != 3 {
+                return Err(parse_error(format!(
+                    "expected `symbol production weight`, got `{}`",
+                    content
+                )));
+            }
+            let symbol = Symbol::from_name(parts[0])
+                .ok_or_else(|| parse_error(format!("unknown symbol `{}`", parts[0])))?;
+            let production = Production::from_name(symbol, parts[1]).ok_or_else(|| {
+                parse_error(format!(
+                    "unknown production `{}` for symbol `{}`",
+                    parts[1], parts[0]
+                ))
+            })?;
+            let weight: f64 = parts[2]
+                .parse()
+                .ok()
+                .filter(|w: &f64| *w >= 0.0 && w.is_finite())
+                .ok_or_else(|| parse_error(format!("invalid weight `{}`", parts[2])))?;
+            grammar = grammar.with_rule(symbol, production, weight);
+        }
+        grammar.build()
+    }
+
+    pub fn load(path: &str) -> Result<Self, GrammarError> {
+        let text = std::fs::read_to_string(path).map_err(GrammarError::Io)?;
+        Grammar::parse(&text)
+    }
+
+    pub fn rules(&self, symbol: Symbol) -> &[Rule] {
+        self.rules.get(&symbol).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn enabled_rules(&self, symbol: Symbol) -> impl Iterator<Item = &Rule> {
+        self.rules(symbol).iter().filter(|rule| rule.weight > 0.0)
+    }
+
+    /// The smallest depth a tree for this symbol can have, leaves being 0.
+    pub fn min_depth(&self, symbol: Symbol) -> u64 {
+        self.min_depths[&symbol]
+    }
+
+    fn production_min_depth(&self, symbol: Symbol, production: Production) -> Option<u64> {
+        let children = production.children(symbol)?;
+        if children.is_empty() {
+            return Some(0);
+        }
+        let mut deepest = 0;
+        for child in children.iter() {
+            deepest = deepest.max(*self.min_depths.get(child)?);
+        }
+        Some(deepest + 1)
+    }
+
+    /// Pick a production that wraps an existing expression of `symbol` in a
+    /// new node, like `if` or `negate`. None if the grammar has none enabled.
+    pub fn choose_wrapper(&self, symbol: Symbol, rng: &mut ExprRng) -> Option<Production> {
+        let wrappers: Vec<&Rule> = self
+            .enabled_rules(symbol)
+            .filter(|rule| rule.production.children(symbol).unwrap().contains(&symbol))
+            .collect();
+        if wrappers.is_empty() {
+            return None;
+        }
+        let weights = WeightedIndex::new(wrappers.iter().map(|rule| rule.weight)).unwrap();
+        Some(wrappers[weights.sample(rng)].production)
+    }
+
+    fn choose(
+        &self,
+        symbol: Symbol,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> Production {
+        let fitting: Vec<&Rule> = self
+            .enabled_rules(symbol)
+            .filter(|rule| {
+                self.production_min_depth(symbol, rule.production)
+                    .is_some_and(|d| d <= max_depth)
+            })
+            .collect();
+        assert!(
+            !fitting.is_empty(),
+            "max depth {} is too small for {}",
+            max_depth,
+            symbol.name()
+        );
+
+        let is_leaf = |rule: &&Rule| rule.production.children(symbol).unwrap().is_empty();
+        let candidates: Vec<&Rule> = match method {
+            GenerationMethod::Full if fitting.iter().any(|r| !is_leaf(r)) => {
+                fitting.into_iter().filter(|r| !is_leaf(r)).collect()
+            }
+            _ => fitting,
+        };
+
+        let weights = WeightedIndex::new(candidates.iter().map(|rule| rule.weight)).unwrap();
+        candidates[weights.sample(rng)].production
+    }
+
+    fn generate_if<T, F>(
+        &self,
+        generator: F,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<T>
+    where
+        T: Clone + 'static,
+        F: Fn(&Self, &mut ExprRng, u64, GenerationMethod) -> BoxedExpr<T>,
+    {
+        Box::new(IfExpr {
+            condition: self.generate_bool(rng, max_depth - 1, method).into(),
+            consequent: generator(self, rng, max_depth - 1, method).into(),
+            alternative: generator(self, rng, max_depth - 1, method).into(),
+        })
+    }
+
+    pub fn generate_action(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<Action> {
+        use Production::*;
+        match self.choose(Symbol::Action, rng, max_depth, method) {
+            Move => Box::new(MoveExpr {
+                direction: self.generate_direction(rng, max_depth - 1, method).into(),
+            }),
+            SetVelocity => Box::new(SetVelocityExpr {
+                target_velocity: self.generate_velocity(rng, max_depth - 1, method).into(),
+                max_energy_ratio: self.generate_fraction(rng, max_depth - 1, method).into(),
+            }),
+            Split => Box::new(SplitExpr {
+                impulse: self.generate_direction(rng, max_depth - 1, method).into(),
+                mass_fraction: self.generate_fraction(rng, max_depth - 1, method).into(),
+            }),
+            Bite => Box::new(BiteExpr {
+                target: self.generate_fish_ref(rng, max_depth - 1, method).into(),
+                size: self.generate_fraction(rng, max_depth - 1, method).into(),
+            }),
+            Pass => Box::new(ConstExpr::new(Action::Pass)),
+            If => self.generate_if(Self::generate_action, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce an action", production),
+        }
+    }
+
+    pub fn generate_direction(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<Vec2> {
+        use Production::*;
+        match self.choose(Symbol::Direction, rng, max_depth, method) {
+            FishDirection => Box::new(FishDirectionExpr {
+                origin: self.generate_fish_ref(rng, max_depth - 1, method).into(),
+                target: self.generate_fish_ref(rng, max_depth - 1, method).into(),
+            }),
+            FoodDirection => Box::new(FoodDirectionExpr),
+            If => self.generate_if(Self::generate_direction, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce a direction", production),
+        }
+    }
+
+    pub fn generate_velocity(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<Velocity> {
+        use Production::*;
+        match self.choose(Symbol::Velocity, rng, max_depth, method) {
+            ScaledDirection => Box::new(MulExpr {
+                left: self.generate_direction(rng, max_depth - 1, method).into(),
+                right: self.generate_fraction(rng, max_depth - 1, method).into(),
+            }),
+            ConstVelocity => {
+                let speed = rng.gen_range(0.0..=MAX_CONST_SPEED);
+                Box::new(ConstExpr::new(Velocity(
+                    Vec2::random_normalized(rng) * speed,
+                )))
+            }
+            Current => Box::new(CurrentExpr),
+            If => self.generate_if(Self::generate_velocity, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce a velocity", production),
+        }
+    }
+
+    pub fn generate_fish_ref(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<FishRef> {
+        use Production::*;
+        match self.choose(Symbol::FishRef, rng, max_depth, method) {
+            GetSelf => Box::new(GetSelfExpr),
+            Nearest => Box::new(DichtsteVisExpr),
+            If => self.generate_if(Self::generate_fish_ref, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce a fish ref", production),
+        }
+    }
+
+    pub fn generate_bool(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<bool> {
+        use Production::*;
+        match self.choose(Symbol::Bool, rng, max_depth, method) {
+            ConstBool => Box::new(ConstExpr::new(rng.gen::<bool>())),
+            LessThan => Box::new(LessThenExpr {
+                left: self.generate_f64(rng, max_depth - 1, method).into(),
+                right: self.generate_f64(rng, max_depth - 1, method).into(),
+            }),
+            Not => Box::new(NotExpr {
+                value: self.generate_bool(rng, max_depth - 1, method).into(),
+            }),
+            If => self.generate_if(Self::generate_bool, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce a bool", production),
+        }
+    }
+
+    pub fn generate_f64(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<NotNan<f64>> {
+        use Production::*;
+        match self.choose(Symbol::F64, rng, max_depth, method) {
+            FishEnergy => Box::new(FishEnergyExpr {
+                fish: self.generate_fish_ref(rng, max_depth - 1, method).into(),
+            }),
+            FoodDistance => Box::new(FoodDistanceExpr),
+            // Log-uniform, spanning the range of fish energies
+            ConstF64 => Box::new(ConstExpr::new(NotNan::from_inner(
+                10f64.powf(rng.gen_range(0.0..5.0)),
+            ))),
+            Negate => Box::new(NegateExpr {
+                value: self.generate_f64(rng, max_depth - 1, method).into(),
+            }),
+            Add => Box::new(AddExpr {
+                left: self.generate_f64(rng, max_depth - 1, method).into(),
+                right: self.generate_f64(rng, max_depth - 1, method).into(),
+            }),
+            If => self.generate_if(Self::generate_f64, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce an f64", production),
+        }
+    }
+
+    pub fn generate_fraction(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<Fraction> {
+        use Production::*;
+        match self.choose(Symbol::Fraction, rng, max_depth, method) {
+            ConstFraction => Box::new(ConstExpr::new(Fraction::from_f64(rng.gen_range(0.0..=1.0)))),
+            ColorSimilarity => Box::new(ColorSimilarityExpr {
+                lhs: self.generate_color(rng, max_depth - 1, method).into(),
+                rhs: self.generate_color(rng, max_depth - 1, method).into(),
+            }),
+            Light => Box::new(LightExpr),
+            Temperature => Box::new(TemperatureExpr),
+            Nutrients => Box::new(NutrientsExpr),
+            If => self.generate_if(Self::generate_fraction, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce a fraction", production),
+        }
+    }
+
+    pub fn generate_color(
+        &self,
+        rng: &mut ExprRng,
+        max_depth: u64,
+        method: GenerationMethod,
+    ) -> BoxedExpr<Color> {
+        use Production::*;
+        match self.choose(Symbol::Color, rng, max_depth, method) {
+            ConstColor => {
+                let color: [f32; 4] = [
+                    rng.gen_range(0.0..=1.0),
+                    rng.gen_range(0.0..=1.0),
+                    rng.gen_range(0.0..=1.0),
+                    1.0,
+                ];
+                Box::new(ConstExpr::new(Color::new(color)))
+            }
+            FishColor => Box::new(FishColorExpr {
+                fish: self.generate_fish_ref(rng, max_depth - 1, method).into(),
+            }),
+            If => self.generate_if(Self::generate_color, rng, max_depth, method),
+            production => unreachable!("{:?} can't produce a color", production),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn parse_error_line(text: &str) -> usize {
+        match Grammar::parse(text) {
+            Err(GrammarError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn default_matches_asset() {
+        let asset = Grammar::parse(include_str!("../../assets/default.grammar")).unwrap();
+        assert_eq!(Grammar::default(), asset);
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(parse_error_line("action move"), 1);
+        assert_eq!(parse_error_line("# comment\n\nfish move 1"), 3);
+        assert_eq!(parse_error_line("action negate 1"), 1);
+        assert_eq!(parse_error_line("action move -1"), 1);
+        assert_eq!(parse_error_line("action move inf"), 1);
+    }
+
+    #[test]
+    fn reports_load_errors() {
+        assert!(matches!(
+            Grammar::load("no/such.grammar"),
+            Err(GrammarError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unproductive_symbols() {
+        // `if` alone never terminates
+        let grammar = Grammar::parse("action pass 1\ndirection if 1");
+        assert!(matches!(
+            grammar,
+            Err(GrammarError::Unproductive(Symbol::Direction))
+        ));
+    }
+
+    #[test]
+    fn builds_min_depths() {
+        let grammar = Grammar::default();
+        assert_eq!(grammar.min_depth(Symbol::Bool), 0);
+        assert_eq!(grammar.min_depth(Symbol::Direction), 0);
+        // Every action has an argument
+        assert_eq!(grammar.min_depth(Symbol::Action), 1);
+    }
+
+    #[test]
+    fn no_bites_when_swallowing_whole() {
+        let grammar = for_predation_mode(Grammar::default());
+        let bite = grammar
+            .rules(Symbol::Action)
+            .iter()
+            .find(|rule| rule.production == Production::Bite)
+            .unwrap();
+        assert_eq!(bite.weight == 0.0, PREDATION_MODE == PredationMode::Cover);
+    }
+
+    #[test]
+    fn wrappers_skip_disabled_productions() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        let text = include_str!("../../assets/default.grammar")
+            .replace("bool        if                  1", "bool if 0")
+            .replace("# bool      not", "bool not")
+            .replace("action      if                  1", "action if 0");
+        let grammar = Grammar::parse(&text).unwrap();
+        for _ in 0..20 {
+            assert_eq!(
+                grammar.choose_wrapper(Symbol::Bool, &mut rng),
+                Some(Production::Not)
+            );
+        }
+        assert_eq!(grammar.choose_wrapper(Symbol::Action, &mut rng), None);
+    }
+    #[test]
+    fn velocities_have_their_own_symbol() {
+        use crate::lang::generators::{generate_minimal_expr, wrap_in_generic};
+
+        assert_eq!(symbol_of::<Vec2>(), Symbol::Direction);
+        assert_eq!(symbol_of::<Velocity>(), Symbol::Velocity);
+        // Building these from direction rules would fail to downcast
+        let mut rng = ExprRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let velocity = generate_minimal_expr::<Velocity>(&mut rng);
+            let wrapped = wrap_in_generic(&*velocity, &mut rng);
+            wrapped.mutate(&mut rng);
+        }
+    }
+}
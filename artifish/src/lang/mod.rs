@@ -1,7 +1,8 @@
 pub mod core;
 pub mod expressions;
 pub mod generators;
+pub mod grammar;
+pub mod perturb;
 
 pub use self::core::*;
 pub use self::expressions::*;
-pub use self::generators::*;
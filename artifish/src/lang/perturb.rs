@@ -0,0 +1,112 @@
+use decorum::NotNan;
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Normal};
+
+use super::core::ExprRng;
+use super::expressions::Fraction;
+use crate::color::{Color, MIN_CHANNEL};
+use crate::vec2::{Vec2, Velocity};
+use crate::{COLOR_PERTURB_SIGMA, F64_PERTURB_LOG_SIGMA, FRACTION_PERTURB_SIGMA};
+use crate::{MAX_CONST_SPEED, VELOCITY_PERTURB_SIGMA};
+
+/// Small random nudges to constant values, as opposed to the structural
+/// mutations that throw a value away and regenerate a subtree.
+pub trait Perturb {
+    fn perturbed(&self, rng: &mut ExprRng) -> Self;
+}
+
+fn gaussian(rng: &mut ExprRng, sigma: f64) -> f64 {
+    Normal::new(0.0, sigma).unwrap().sample(rng)
+}
+
+impl Perturb for NotNan<f64> {
+    // Multiplicative log-normal step, so the step scales with the magnitude
+    // and the sign is preserved. Zero would be stuck, so it gets a nudge.
+    fn perturbed(&self, rng: &mut ExprRng) -> Self {
+        let value = self.into_inner();
+        if value == 0.0 {
+            return NotNan::from_inner(gaussian(rng, F64_PERTURB_LOG_SIGMA));
+        }
+        let factor: f64 = LogNormal::new(0.0, F64_PERTURB_LOG_SIGMA)
+            .unwrap()
+            .sample(rng);
+        NotNan::from_inner(value * factor)
+    }
+}
+
+impl Perturb for Fraction {
+    fn perturbed(&self, rng: &mut ExprRng) -> Self {
+        let value = self.to_f64() + gaussian(rng, FRACTION_PERTURB_SIGMA);
+        Fraction::from_f64(value.clamp(0.0, 1.0))
+    }
+}
+
+impl Perturb for Color {
+    fn perturbed(&self, rng: &mut ExprRng) -> Self {
+        let mut color = self.inner;
+        for channel in color.iter_mut().take(3) {
+            *channel += gaussian(rng, COLOR_PERTURB_SIGMA) as f32;
+            *channel = channel.clamp(MIN_CHANNEL, 1.0);
+        }
+        Color::new(color)
+    }
+}
+
+impl Perturb for Velocity {
+    // Keep to the speeds constant velocities are generated with
+    fn perturbed(&self, rng: &mut ExprRng) -> Self {
+        let Velocity(velocity) = self;
+        let vector = Vec2::new(
+            velocity.x + gaussian(rng, VELOCITY_PERTURB_SIGMA),
+            velocity.y + gaussian(rng, VELOCITY_PERTURB_SIGMA),
+        );
+        if vector.length() > MAX_CONST_SPEED {
+            Velocity(vector.normalized() * MAX_CONST_SPEED)
+        } else {
+            Velocity(vector)
+        }
+    }
+}
+
+/// Flip a coin to decide whether a constant gets fine-tuned rather than
+/// structurally mutated.
+pub fn should_perturb(rng: &mut ExprRng) -> bool {
+    rng.gen_bool(crate::PERTURB_RATE)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn perturbations_stay_in_range() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            for value in [0.0, 0.01, 0.5, 0.99, 1.0] {
+                let fraction = Fraction::from_f64(value).perturbed(&mut rng).to_f64();
+                assert!((0.0..=1.0).contains(&fraction));
+            }
+
+            let color = Color::new([0.1, 0.5, 1.0, 0.7]).perturbed(&mut rng);
+            for channel in color.inner.iter().take(3) {
+                assert!((MIN_CHANNEL..=1.0).contains(channel));
+            }
+            assert_eq!(color.inner[3], 0.7);
+
+            let value = NotNan::from_inner(-3.0).perturbed(&mut rng).into_inner();
+            assert!(value < 0.0);
+
+            let Velocity(velocity) = Velocity(Vec2::new(0.6, -0.8)).perturbed(&mut rng);
+            assert!(velocity.length() <= MAX_CONST_SPEED + 1e-9);
+        }
+    }
+
+    #[test]
+    fn zero_gets_unstuck() {
+        let mut rng = ExprRng::seed_from_u64(0);
+        let zero = NotNan::from_inner(0.0);
+        assert!((0..10).any(|_| zero.perturbed(&mut rng) != zero));
+    }
+}
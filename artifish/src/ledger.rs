@@ -0,0 +1,153 @@
+// Energy bookkeeping. Energy only enters the aquarium through growth and
+// spawning, and only leaves it by being spent or by fish disappearing, so the
+// total should always match what the ledger says.
+
+use std::ops::AddAssign;
+
+/// Where energy came from and where it went, over one tick or summed over
+/// many. All amounts are positive in the direction their name says.
+#[derive(Clone, Debug, Default)]
+pub struct EnergyLedger {
+    // Sources
+    pub growth: f64,
+    pub spawned_fish: f64,
+    pub spawned_food: f64,
+    /// Dead fishes' bodies, added to their corpses.
+    pub remains: f64,
+
+    // Sinks
+    pub movement: f64,
+    pub split_cost: f64,
+    pub failed_split: f64,
+    pub leaked: f64,
+    pub starved: f64,
+    pub digestion: f64,
+    pub decay: f64,
+
+    // Transfers, these move energy around without changing the total
+    pub eaten: f64,
+    pub food_eaten: f64,
+    pub offspring: f64,
+    pub bitten: f64,
+    pub died: f64,
+    pub scavenged: f64,
+    pub decomposed: f64,
+}
+
+impl EnergyLedger {
+    pub const CSV_HEADER: &'static str = "growth,spawned_fish,spawned_food,remains,movement,\
+                                          split_cost,failed_split,leaked,starved,digestion,\
+                                          decay,eaten,food_eaten,offspring,bitten,died,\
+                                          scavenged,decomposed";
+
+    pub fn sources(&self) -> f64 {
+        self.growth + self.spawned_fish + self.spawned_food + self.remains
+    }
+
+    pub fn sinks(&self) -> f64 {
+        self.movement
+            + self.split_cost
+            + self.failed_split
+            + self.leaked
+            + self.starved
+            + self.digestion
+            + self.decay
+    }
+
+    /// How much the total energy should have changed.
+    pub fn net(&self) -> f64 {
+        self.sources() - self.sinks()
+    }
+
+    pub fn csv_row(&self) -> String {
+        [
+            self.growth,
+            self.spawned_fish,
+            self.spawned_food,
+            self.remains,
+            self.movement,
+            self.split_cost,
+            self.failed_split,
+            self.leaked,
+            self.starved,
+            self.digestion,
+            self.decay,
+            self.eaten,
+            self.food_eaten,
+            self.offspring,
+            self.bitten,
+            self.died,
+            self.scavenged,
+            self.decomposed,
+        ]
+        .iter()
+        .map(|amount| format!("{:.1}", amount))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+
+    /// Panic if the total energy did not change by what the books say, up to
+    /// rounding errors.
+    pub fn check_balance(&self, total_before: f64, total_after: f64) {
+        let expected = total_before + self.net();
+        let tolerance = 1e-9 * (total_before.abs() + self.sources() + self.sinks()).max(1.0);
+        assert!(
+            (total_after - expected).abs() <= tolerance,
+            "energy books don't balance: expected {}, have {} ({:?})",
+            expected,
+            total_after,
+            self
+        );
+    }
+}
+
+impl AddAssign<&EnergyLedger> for EnergyLedger {
+    fn add_assign(&mut self, other: &EnergyLedger) {
+        self.growth += other.growth;
+        self.spawned_fish += other.spawned_fish;
+        self.spawned_food += other.spawned_food;
+        self.remains += other.remains;
+        self.movement += other.movement;
+        self.split_cost += other.split_cost;
+        self.failed_split += other.failed_split;
+        self.leaked += other.leaked;
+        self.starved += other.starved;
+        self.digestion += other.digestion;
+        self.decay += other.decay;
+        self.eaten += other.eaten;
+        self.food_eaten += other.food_eaten;
+        self.offspring += other.offspring;
+        self.bitten += other.bitten;
+        self.died += other.died;
+        self.scavenged += other.scavenged;
+        self.decomposed += other.decomposed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ledger() -> EnergyLedger {
+        EnergyLedger {
+            growth: 10.0,
+            movement: 3.0,
+            digestion: 2.0,
+            // Transfers don't count towards the balance
+            eaten: 50.0,
+            bitten: 8.0,
+            ..EnergyLedger::default()
+        }
+    }
+
+    #[test]
+    fn balanced_books_pass() {
+        ledger().check_balance(100.0, 105.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "energy books don't balance")]
+    fn unbalanced_books_panic() {
+        ledger().check_balance(100.0, 106.0);
+    }
+}
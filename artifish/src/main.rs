@@ -11,65 +11,252 @@ extern crate artifish_derive;
 
 use decorum::NotNan;
 use glutin_window::GlutinWindow as Window;
-use graphics::ellipse::Border;
 use opengl_graphics::{Filter, GlGraphics, GlyphCache, OpenGL, TextureSettings};
 use piston::event_loop::{EventSettings, Events};
-use piston::input::{RenderArgs, RenderEvent, UpdateArgs, UpdateEvent};
+use piston::input::{MouseCursorEvent, MouseScrollEvent, PressEvent, ReleaseEvent, TextEvent};
+use piston::input::{RenderEvent, UpdateArgs, UpdateEvent};
 use piston::window::WindowSettings;
 use rand::Rng;
 use rand_chacha::ChaCha20Rng;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
 
 mod angels;
+mod camera;
+mod clock;
 mod color;
+mod coloring;
+mod corpses;
+mod environment;
 mod fish;
+mod food;
+mod history;
+mod hud;
+mod input;
 mod lang;
+mod ledger;
+mod metrics;
+mod obstacles;
+mod options;
+mod physics;
+mod raster;
+mod rewind;
 mod state;
+mod tui;
 mod vec2;
 
+use camera::Camera;
+use clock::{Clock, TIME_STEP};
 use color::Color;
-use fish::Fish;
+use coloring::ColorMode;
+use fish::{Action, Fish, FishIds, PredationMode};
+use lang::grammar::{set_grammar, Grammar};
 use lang::Program;
+use obstacles::load_scenario;
+use options::{Options, USAGE};
+use physics::BoundaryMode;
+use raster::Rasterizer;
+use rewind::Rewind;
 use state::State;
 use vec2::Vec2;
 
 const N_TICKS: u8 = 20;
+// Simulation speed multipliers selectable with [ and ]
+const MIN_SPEED: f64 = 0.125;
+const MAX_SPEED: f64 = 16.0;
+// Don't fall further and further behind when ticks take longer than real time
+const MAX_TICKS_PER_UPDATE: u32 = 100;
+// In fast mode, simulate this many seconds of wall time between frames
+const FAST_MODE_FRAME_TIME: f64 = 0.25;
 
-const MAX_X: f64 = 800.0;
-const MAX_Y: f64 = 600.0;
+// Size of the aquarium, which the camera can pan and zoom over
+const MAX_X: f64 = 2400.0;
+const MAX_Y: f64 = 1800.0;
+
+// Size of the window and of headless frames, independent of the aquarium
+const WINDOW_WIDTH: f64 = 800.0;
+const WINDOW_HEIGHT: f64 = 600.0;
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 10.0;
+// Screen pixels per arrow key press, and zoom factor per scroll step
+const PAN_STEP: f64 = 50.0;
+const ZOOM_STEP: f64 = 1.2;
+// Side panel for the fish clicked on
+const PANEL_WIDTH: f64 = 320.0;
+const PANEL_FONT_SIZE: u32 = 14;
+// A press and release closer than this is a click, not a drag
+const CLICK_DISTANCE: f64 = 3.0;
+// Energy and age (in ticks) at the hot end of the colour scales
+const HEATMAP_MAX_ENERGY: f64 = 10_000.0;
+const HEATMAP_MAX_AGE: u64 = 2_000;
+// Debug overlays: ticks of trail kept per fish, and how long the drawn
+// vectors are, in seconds of travel at the velocity (or velocity change)
+const TRAIL_LENGTH: usize = 40;
+const VECTOR_SECONDS: f64 = 1.0;
+// Population graphs: ticks between samples, and samples kept
+const HISTORY_INTERVAL: u64 = 10;
+const HISTORY_LENGTH: usize = 400;
+const GRAPH_WIDTH: f64 = 360.0;
+const GRAPH_HEIGHT: f64 = 80.0;
+// Energy given or taken per key press with the god tools
+const GOD_ENERGY_STEP: f64 = 1000.0;
+// Snapshots to rewind to: ticks between them, and how many are kept
+const REWIND_INTERVAL: u64 = 200;
+const REWIND_LENGTH: usize = 60;
 
 const MOVE_SPEED: f64 = 100.0;
 const FISH_SPLIT_AT_SIZE: f64 = 90_000.0 * 1.0;
 const FISH_GROWTH_FACTOR: f64 = 1.0;
+// Turn off to make fishes live off food only
+const UNIFORM_GROWTH: bool = true;
+const FOOD_SPAWN_RATE: f64 = 10.0;
+const FOOD_PARTICLE_ENERGY: f64 = 500.0;
+const MAX_FOOD_PARTICLES: usize = 200;
+// Fishes with less energy than this die, leaving a corpse if CORPSES is set
+const STARVATION_ENERGY: f64 = 5.0;
+const CORPSES: bool = true;
+// Share of its largest size a dead fish's body adds to its corpse
+const CORPSE_BODY_FRACTION: f64 = 0.5;
+const CORPSE_HALF_LIFE: f64 = 30.0;
+const CORPSE_SINK_SPEED: f64 = 0.05;
+// Rotting corpses turn into food instead of just disappearing
+const CORPSE_DECAY_TO_FOOD: bool = true;
+// Day/night and seasons in seconds, zero turns them off. See environment.rs
+const DAY_LENGTH: f64 = 60.0;
+const NIGHT_LIGHT: f64 = 0.2;
+const SEASON_LENGTH: f64 = 600.0;
+const WINTER_TEMPERATURE: f64 = 0.5;
+// Extra cost in the coldest water and poorest patches
+const COLD_MOVE_COST: f64 = 1.0;
+const POOR_SPLIT_COST: f64 = 1.0;
 const FISH_GENERATION_RATE: f64 = 2.0 / 1.0;
 const SPLIT_COST_FACTOR: f64 = 1.0;
 const BASE_SPLIT_COST: f64 = 69.0;
 const MUTATION_RATE: f64 = 0.1;
 // const DIE_ON_AMBITIOUS_BABY: bool = true;
 const DIE_ON_AMBITIOUS_BABY: bool = false;
+// Cover swallows fishes whole, Bite lets them nibble each other, see fish.rs
+const PREDATION_MODE: PredationMode = PredationMode::Both;
+// Reach in pixels beyond the edge, energy per second per unit of surface area
+const BITE_REACH: f64 = 5.0;
+const BITE_RATE: f64 = 5.0;
+const MAX_BITE_FRACTION: f64 = 0.25;
+const BITE_EFFICIENCY: f64 = 0.7;
+// Quadratic drag, see physics.rs
+const FLUID_DENSITY: f64 = 5.0;
+const DRAG_COEFFICIENT: f64 = 0.47;
+// Downwards acceleration, and how much of it buoyancy cancels
+const GRAVITY: f64 = 0.1;
+const BUOYANCY: f64 = 0.95;
+// Peak speed of the whirl in the middle of the aquarium, see environment.rs
+const VORTEX_SPEED: f64 = 0.3;
+// Soft-body contacts between overlapping fish, zero stiffness disables them
+const COLLISION_STIFFNESS: f64 = 2.0;
+const COLLISION_DAMPING: f64 = 5.0;
+const BOUNDARY_MODE: BoundaryMode = BoundaryMode::Clamp;
+// Chance that mutating a constant nudges its value instead of replacing it
+const PERTURB_RATE: f64 = 0.5;
+const F64_PERTURB_LOG_SIGMA: f64 = 0.1;
+const FRACTION_PERTURB_SIGMA: f64 = 0.05;
+const COLOR_PERTURB_SIGMA: f64 = 0.05;
+const VELOCITY_PERTURB_SIGMA: f64 = 0.1;
+// Constant velocities are generated and perturbed up to this speed
+const MAX_CONST_SPEED: f64 = 1.0;
+// Bloat control: offspring programs exceeding these are rejected
+const MAX_PROGRAM_SIZE: u64 = 200;
+const MAX_PROGRAM_DEPTH: u64 = 14;
+const MAX_MUTATION_ATTEMPTS: u32 = 10;
+const HOIST_MUTATION_RATE: f64 = 0.1;
+const SHRINK_MUTATION_RATE: f64 = 0.1;
+
+// Load the program grammar from this file instead of using the default one
+const GRAMMAR_PATH: Option<&str> = None;
+const INITIAL_PROGRAM_MIN_DEPTH: u64 = 2;
+const INITIAL_PROGRAM_MAX_DEPTH: u64 = 6;
+
+// Load obstacles from this file, see obstacles.rs and assets/reef.scenario
+const SCENARIO_PATH: Option<&str> = None;
+
+const METRICS_INTERVAL: u64 = 100;
+// Check every tick that no energy appears or disappears unaccounted for
+const STRICT_ENERGY_LEDGER: bool = cfg!(debug_assertions);
+const METRICS_PATH: &str = "metrics.csv";
+
+const FONT_PATH: &str = "assets/ZenLoop-Italic.ttf";
+// Ticks between frames written with --frames
+const FRAME_INTERVAL: u64 = 20;
 
 fn main() {
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        std::process::exit(2);
+    });
+
+    if let Some(path) = GRAMMAR_PATH {
+        let grammar = Grammar::load(path)
+            .unwrap_or_else(|err| panic!("Could not load grammar {}: {}", path, err));
+        set_grammar(grammar).expect("Grammar was already initialised");
+    }
+
+    let seed: u64 = 127002;
+    let mut state = State::new(seed);
+    if let Some(path) = SCENARIO_PATH {
+        state.obstacles = load_scenario(path)
+            .unwrap_or_else(|err| panic!("Could not load scenario {}: {}", path, err));
+    }
+
+    if let Some(ticks) = options.headless {
+        run_headless(&mut state, ticks, options.frames.as_deref());
+        write_metrics(&state);
+        return;
+    }
+
+    if options.tui {
+        if let Err(err) = tui::run(&mut state) {
+            eprintln!("Terminal error: {}", err);
+        }
+        write_metrics(&state);
+        return;
+    }
+
     // Change this to OpenGL::V2_1 if not working.
     let opengl = OpenGL::V3_2;
 
     // Create an Glutin window.
-    let mut window: Window = WindowSettings::new("static-but-internal-circle", [MAX_X, MAX_Y])
-        .graphics_api(opengl)
-        .exit_on_esc(true)
-        .build()
-        .unwrap();
+    let mut window: Window =
+        WindowSettings::new("static-but-internal-circle", [WINDOW_WIDTH, WINDOW_HEIGHT])
+            .graphics_api(opengl)
+            .exit_on_esc(true)
+            .build()
+            .unwrap();
 
     let texture_settings = TextureSettings::new().filter(Filter::Nearest);
-    let glyphs = GlyphCache::new("assets/ZenLoop-Italic.ttf", (), texture_settings)
-        .expect("Could not load font");
-
-    let seed: u64 = 127002;
+    let glyphs = GlyphCache::new(FONT_PATH, (), texture_settings).expect("Could not load font");
 
     // Create a new game and run it.
     let mut app = App {
         gl: GlGraphics::new(opengl),
-        state: State::new(seed),
-        elapsed_time: 0.0,
+        state,
+        clock: Clock::new(),
+        paused: false,
+        speed: 1.0,
+        fast_mode: false,
         glyph_cache: glyphs,
+        camera: Camera::new([WINDOW_WIDTH, WINDOW_HEIGHT]),
+        cursor: [0.0, 0.0],
+        dragging: false,
+        drag_distance: 0.0,
+        selected: None,
+        color_mode: ColorMode::Inherited,
+        show_trails: false,
+        show_vectors: false,
+        show_links: false,
+        trails: HashMap::new(),
+        show_graphs: false,
+        dragged_fish: None,
+        tag_input: None,
+        rewind: Rewind::new(REWIND_INTERVAL, REWIND_LENGTH),
+        seed,
     };
 
     let mut events = Events::new(EventSettings::new());
@@ -81,6 +268,57 @@ fn main() {
         if let Some(args) = e.update_args() {
             app.update(&args);
         }
+
+        if let Some(button) = e.press_args() {
+            app.press(button);
+        }
+
+        if let Some(button) = e.release_args() {
+            app.release(button);
+        }
+
+        if let Some(position) = e.mouse_cursor_args() {
+            app.move_cursor(position);
+        }
+
+        if let Some(scroll) = e.mouse_scroll_args() {
+            app.scroll(scroll);
+        }
+
+        if let Some(text) = e.text_args() {
+            app.type_text(&text);
+        }
+    }
+
+    write_metrics(&app.state);
+}
+
+fn write_metrics(state: &State) {
+    if let Err(err) = state.metrics.write_csv(METRICS_PATH) {
+        eprintln!("Could not write metrics to {}: {}", METRICS_PATH, err);
+    }
+}
+
+/// Simulate without a window, saving frames to `frames_path` if it's set.
+fn run_headless(state: &mut State, ticks: u64, frames_path: Option<&str>) {
+    let frames = frames_path.map(|path| {
+        std::fs::create_dir_all(path)
+            .unwrap_or_else(|err| panic!("Could not create {}: {}", path, err));
+        let rasterizer = Rasterizer::new(FONT_PATH).expect("Could not load font");
+        (path, rasterizer)
+    });
+    let camera = Camera::new([WINDOW_WIDTH, WINDOW_HEIGHT]);
+
+    for _ in 0..ticks {
+        state.update(TIME_STEP);
+        if let Some((path, rasterizer)) = &frames {
+            if state.tick.is_multiple_of(FRAME_INTERVAL) {
+                let frame = format!("{}/frame_{:06}.png", path, state.tick / FRAME_INTERVAL);
+                rasterizer
+                    .save_png(state, &camera, ColorMode::Inherited, &frame)
+                    .unwrap_or_else(|err| panic!("Could not save {}: {}", frame, err));
+            }
+        }
     }
 }
 
@@ -88,94 +326,101 @@ pub struct App<'a> {
     gl: GlGraphics, // OpenGL drawing backend.
     state: State,
     glyph_cache: GlyphCache<'a>,
-    elapsed_time: f64,
+    clock: Clock,
+    paused: bool,
+    speed: f64,
+    // Run ticks as fast as possible, rendering only now and then
+    fast_mode: bool,
+    camera: Camera,
+    // Mouse position in window coordinates, and whether it's dragging the view
+    cursor: [f64; 2],
+    dragging: bool,
+    drag_distance: f64,
+    // Id of the fish shown in the side panel
+    selected: Option<u64>,
+    color_mode: ColorMode,
+    // Debug overlays
+    show_trails: bool,
+    show_vectors: bool,
+    show_links: bool,
+    // Recent positions of each fish, oldest first
+    trails: HashMap<u64, VecDeque<Vec2>>,
+    show_graphs: bool,
+    // Fish being moved with the right mouse button
+    dragged_fish: Option<u64>,
+    // Tag being typed for the selected fish
+    tag_input: Option<String>,
+    rewind: Rewind,
+    // Seed of the current timeline
+    seed: u64,
+}
+
+fn generate_fish(rng: &mut ChaCha20Rng, ids: &mut FishIds) -> Fish {
+    let program = Program::random(rng, 6);
+    generate_fish_with_program(rng, ids, program)
 }
 
-fn generate_fish(rng: &mut ChaCha20Rng) -> Fish {
+fn generate_fish_with_program(rng: &mut ChaCha20Rng, ids: &mut FishIds, program: Program) -> Fish {
     let x = rng.gen_range(0.0..MAX_X);
     let y = rng.gen_range(0.0..MAX_Y);
     let radius = rng.gen_range(5.0..1000.0);
-    let program = Program::random(rng, 6);
+    let id = ids.take();
     Fish {
+        id,
+        founder: id,
         x,
         y,
         energy: NotNan::from_inner(radius),
+        peak_energy: NotNan::from_inner(radius),
         velocity: Vec2::zero(),
+        program_hash: program.shape_hash(),
         program,
         color: Color::random(rng),
         is_man_made: false,
         tag: None,
+        age: 0,
+        last_action: Action::Pass,
     }
 }
 
 impl<'a> App<'a> {
-    fn render(&mut self, args: &RenderArgs) {
-        use graphics::*;
-
-        let fishes = &self.state.fishes;
-
-        let glyph_cache = &mut self.glyph_cache;
-
-        self.gl.draw(args.viewport(), |c, gl| {
-            // Clear the screen.
-            clear(Color::BLACK.into(), gl);
-
-            let identity = c.transform;
-
-            for fish in fishes.iter().rev() {
-                let fish_color_dark = fish.color.darken(0.5);
-                let cell = ellipse::circle(fish.x, fish.y, fish.radius());
-                let cell_border = Border {
-                    color: fish_color_dark.into(),
-                    radius: 1.0,
-                };
-                Ellipse::new(fish.color.into()).border(cell_border).draw(
-                    cell,
-                    &Default::default(),
-                    identity,
-                    gl,
-                );
-
-                let center = ellipse::circle(fish.x, fish.y, 1.5);
-                ellipse(fish_color_dark.into(), center, identity, gl);
-
-                if let Some(ref tag) = fish.tag {
-                    text(
-                        Color::WHITE.into(),
-                        20,
-                        tag,
-                        glyph_cache,
-                        identity.trans(fish.x, fish.y),
-                        gl,
-                    )
-                    .unwrap();
-                }
-
-                // let t = identity.trans(100.0, 100.0);
-                // text(Color::RED.into(), 100, "tetten", glyph_cache, t, gl).unwrap();
-
-                let t = identity.trans(MAX_X - 100.0, MAX_Y - 100.0);
-                text(
-                    Color::RED.into(),
-                    30,
-                    &fishes.len().to_string(),
-                    glyph_cache,
-                    t,
-                    gl,
-                )
-                .unwrap();
+    pub fn update(&mut self, args: &UpdateArgs) {
+        if self.paused {
+            self.clock.reset();
+        } else if self.fast_mode {
+            let start = Instant::now();
+            while start.elapsed().as_secs_f64() < FAST_MODE_FRAME_TIME {
+                self.tick();
+            }
+            self.clock.reset();
+        } else {
+            for _ in 0..self.clock.ticks_due(args.dt, self.speed) {
+                self.tick();
             }
-        });
+        }
+        self.clock.update_tps();
     }
 
-    pub fn update(&mut self, args: &UpdateArgs) {
-        let time_step = 1.0 / (N_TICKS as f64);
-        self.elapsed_time += args.dt;
+    fn tick(&mut self) {
+        self.rewind.resume();
+        self.state.update(TIME_STEP);
+        self.rewind.record(&self.state);
+        self.clock.count_tick();
+        if self.show_trails {
+            self.record_trails();
+        }
+    }
 
-        if self.elapsed_time > time_step {
-            // easy mode
-            self.elapsed_time = 0.0;
-            self.state.update(time_step);
+    fn record_trails(&mut self) {
+        let fishes = &self.state.fishes;
+        self.trails
+            .retain(|id, _| fishes.iter().any(|fish| fish.id == *id));
+        for fish in fishes.iter() {
+            let trail = self.trails.entry(fish.id).or_default();
+            if trail.len() == TRAIL_LENGTH {
+                trail.pop_front();
+            }
+            trail.push_back(Vec2::new(fish.x, fish.y));
         }
     }
 }
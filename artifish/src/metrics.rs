@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::fish::Fish;
+use crate::ledger::EnergyLedger;
+
+/// Summary of the population at one point in time.
+#[derive(Clone, Debug)]
+pub struct MetricsSample {
+    pub tick: u64,
+    pub population: usize,
+    pub program_size_min: u64,
+    pub program_size_median: u64,
+    pub program_size_p90: u64,
+    pub program_size_max: u64,
+    pub program_size_mean: f64,
+    pub program_depth_mean: f64,
+    /// Energy ledger summed over all ticks so far.
+    pub energy: EnergyLedger,
+}
+
+impl MetricsSample {
+    pub fn from_fishes(tick: u64, fishes: &[Fish], energy: &EnergyLedger) -> Self {
+        let mut sizes: Vec<u64> = fishes.iter().map(|f| f.program.size()).collect();
+        sizes.sort_unstable();
+        let depths: Vec<u64> = fishes.iter().map(|f| f.program.depth()).collect();
+
+        let percentile = |p: f64| -> u64 {
+            if sizes.is_empty() {
+                return 0;
+            }
+            let ix = ((sizes.len() - 1) as f64 * p).round() as usize;
+            sizes[ix]
+        };
+
+        MetricsSample {
+            tick,
+            population: fishes.len(),
+            program_size_min: percentile(0.0),
+            program_size_median: percentile(0.5),
+            program_size_p90: percentile(0.9),
+            program_size_max: percentile(1.0),
+            program_size_mean: mean(&sizes),
+            program_depth_mean: mean(&depths),
+            energy: energy.clone(),
+        }
+    }
+}
+
+fn mean(values: &[u64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<u64>() as f64 / values.len() as f64
+}
+
+/// Time series of population statistics, sampled every `interval` ticks.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    pub interval: u64,
+    pub samples: Vec<MetricsSample>,
+}
+
+impl Metrics {
+    pub fn new(interval: u64) -> Self {
+        Metrics {
+            interval,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, fishes: &[Fish], energy: &EnergyLedger) {
+        // After going back in time, forget the samples of the abandoned future
+        while self
+            .samples
+            .last()
+            .is_some_and(|sample| sample.tick >= tick)
+        {
+            self.samples.pop();
+        }
+        if tick.is_multiple_of(self.interval) {
+            self.samples
+                .push(MetricsSample::from_fishes(tick, fishes, energy));
+        }
+    }
+
+    pub fn write_csv(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(
+            out,
+            "tick,population,program_size_min,program_size_median,program_size_p90,\
+             program_size_max,program_size_mean,program_depth_mean,{}",
+            EnergyLedger::CSV_HEADER
+        )?;
+        for s in self.samples.iter() {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{:.3},{:.3},{}",
+                s.tick,
+                s.population,
+                s.program_size_min,
+                s.program_size_median,
+                s.program_size_p90,
+                s.program_size_max,
+                s.program_size_mean,
+                s.program_depth_mean,
+                s.energy.csv_row(),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+// Rocks and reefs. Obstacles never move; fishes bump into them and can't
+// see through them.
+
+use std::fmt;
+
+use crate::fish::Fish;
+use crate::vec2::Vec2;
+
+#[derive(Clone, Debug)]
+pub enum Obstacle {
+    Circle {
+        center: Vec2,
+        radius: f64,
+    },
+    /// A convex polygon, vertices in order around it.
+    Polygon {
+        vertices: Vec<Vec2>,
+    },
+}
+
+fn cross(a: Vec2, b: Vec2) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+fn closest_point_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length_squared = ab.dot(&ab);
+    if length_squared == 0.0 {
+        return a;
+    }
+    let t = ((point - a).dot(&ab) / length_squared).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| cross(q - p, r - p);
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+impl Obstacle {
+    fn edges(vertices: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let next = vertices.iter().cycle().skip(1);
+        vertices.iter().copied().zip(next.copied())
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            Obstacle::Circle { center, radius } => (point - *center).length() < *radius,
+            Obstacle::Polygon { vertices } => {
+                // Count edge crossings of a ray going right from the point
+                let mut inside = false;
+                for (a, b) in Obstacle::edges(vertices) {
+                    if (a.y > point.y) != (b.y > point.y) {
+                        let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                        if point.x < x {
+                            inside = !inside;
+                        }
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// The point on the obstacle's outline closest to `point`.
+    fn closest_point(&self, point: Vec2) -> Vec2 {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let offset = point - *center;
+                if offset.length() == 0.0 {
+                    return *center + Vec2::new(*radius, 0.0);
+                }
+                *center + offset.normalized() * *radius
+            }
+            Obstacle::Polygon { vertices } => Obstacle::edges(vertices)
+                .map(|(a, b)| closest_point_on_segment(point, a, b))
+                .min_by(|p, q| (*p - point).length().total_cmp(&(*q - point).length()))
+                .unwrap_or(point),
+        }
+    }
+
+    /// Push a fish that overlaps the obstacle back out, and stop it from
+    /// moving further into it.
+    pub fn collide(&self, fish: &mut Fish) {
+        let position = Vec2::new(fish.x, fish.y);
+        let radius = fish.radius();
+        let surface = self.closest_point(position);
+        let to_fish = position - surface;
+        let distance = to_fish.length();
+
+        let inside = self.contains(position);
+        if !inside && distance >= radius {
+            return;
+        }
+
+        // Outward normal at the surface point
+        let normal = match (inside, distance > 0.0) {
+            (_, false) => match self {
+                Obstacle::Circle { center, .. } => (surface - *center).normalized(),
+                Obstacle::Polygon { .. } => Vec2::new(1.0, 0.0),
+            },
+            (true, true) => to_fish * -1.0 / distance,
+            (false, true) => to_fish / distance,
+        };
+
+        let target = surface + normal * radius;
+        fish.move_to(target.x, target.y);
+        let inward_speed = fish.velocity.dot(&normal);
+        if inward_speed < 0.0 {
+            fish.velocity -= normal * inward_speed;
+        }
+    }
+
+    /// Whether the obstacle lies on the straight line between two points.
+    pub fn blocks(&self, from: Vec2, to: Vec2) -> bool {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                (closest_point_on_segment(*center, from, to) - *center).length() < *radius
+            }
+            Obstacle::Polygon { vertices } => {
+                self.contains(from)
+                    || self.contains(to)
+                    || Obstacle::edges(vertices).any(|(a, b)| segments_intersect(from, to, a, b))
+            }
+        }
+    }
+}
+
+/// Whether `to` can be seen from `from`. Lines of sight are not wrapped in
+/// a toroidal aquarium, so obstacles near the far edge don't block views
+/// across it.
+pub fn line_of_sight(obstacles: &[Obstacle], from: Vec2, to: Vec2) -> bool {
+    !obstacles.iter().any(|obstacle| obstacle.blocks(from, to))
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "could not read scenario: {}", err),
+            ScenarioError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+/// Parse obstacles from a scenario, one per line, e.g.
+/// ```text
+/// # circle  x    y    radius
+/// circle    200  300  40
+/// # polygon x1 y1  x2 y2  x3 y3 ...
+/// polygon   500 100  600 150  550 250
+/// ```
+pub fn parse_scenario(text: &str) -> Result<Vec<Obstacle>, ScenarioError> {
+    let mut obstacles = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_num = i + 1;
+        let content = line.split('#').next().unwrap_or("").trim();
+        if content.is_empty() {
+            continue;
+        }
+        let parse_error = |message: String| ScenarioError::Parse {
+            line: line_num,
+            message,
+        };
+
+        let mut parts = content.split_whitespace();
+        let kind = parts.next().unwrap();
+        let numbers = parts
+            .map(|part| {
+                part.parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .ok_or_else(|| parse_error(format!("invalid number `{}`", part)))
+            })
+            .collect::<Result<Vec<f64>, _>>()?;
+
+        let obstacle = match kind {
+            "circle" => match numbers[..] {
+                [x, y, radius] if radius > 0.0 => Obstacle::Circle {
+                    center: Vec2::new(x, y),
+                    radius,
+                },
+                _ => return Err(parse_error("expected `circle x y radius`".to_owned())),
+            },
+            "polygon" => {
+                if numbers.len() < 6 || numbers.len() % 2 != 0 {
+                    return Err(parse_error(
+                        "expected `polygon x1 y1 x2 y2 x3 y3 ...`".to_owned(),
+                    ));
+                }
+                let vertices = numbers
+                    .chunks(2)
+                    .map(|xy| Vec2::new(xy[0], xy[1]))
+                    .collect();
+                Obstacle::Polygon { vertices }
+            }
+            kind => return Err(parse_error(format!("unknown obstacle `{}`", kind))),
+        };
+        obstacles.push(obstacle);
+    }
+    Ok(obstacles)
+}
+
+pub fn load_scenario(path: &str) -> Result<Vec<Obstacle>, ScenarioError> {
+    let text = std::fs::read_to_string(path).map_err(ScenarioError::Io)?;
+    parse_scenario(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scenario() {
+        let obstacles =
+            parse_scenario("# a reef\ncircle 200 300 40\npolygon 0 0  10 0  10 10  # triangle\n")
+                .unwrap();
+        assert_eq!(obstacles.len(), 2);
+        assert!(parse_scenario("circle 1 2").is_err());
+        assert!(parse_scenario("polygon 0 0 1 1").is_err());
+        assert!(parse_scenario("blob 0 0 1").is_err());
+        assert!(parse_scenario(include_str!("../assets/reef.scenario")).is_ok());
+    }
+
+    #[test]
+    fn obstacles_block_line_of_sight() {
+        let rock = Obstacle::Circle {
+            center: Vec2::new(50.0, 0.0),
+            radius: 10.0,
+        };
+        let wall = Obstacle::Polygon {
+            vertices: vec![
+                Vec2::new(40.0, 20.0),
+                Vec2::new(60.0, 20.0),
+                Vec2::new(60.0, 40.0),
+                Vec2::new(40.0, 40.0),
+            ],
+        };
+        let obstacles = [rock, wall];
+
+        assert!(!line_of_sight(
+            &obstacles,
+            Vec2::zero(),
+            Vec2::new(100.0, 0.0)
+        ));
+        assert!(!line_of_sight(
+            &obstacles,
+            Vec2::new(0.0, 30.0),
+            Vec2::new(100.0, 30.0)
+        ));
+        assert!(line_of_sight(
+            &obstacles,
+            Vec2::new(0.0, 15.0),
+            Vec2::new(100.0, 15.0)
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+// Command line options. Everything else is set with the constants in
+// main.rs.
+
+use std::fmt;
+
+pub const USAGE: &str = "usage: artifish [--tui | --headless TICKS [--frames DIR]]";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    /// Run this many ticks without a window, see raster.rs.
+    pub headless: Option<u64>,
+    /// Directory to write a png of the whole aquarium to every
+    /// `FRAME_INTERVAL` ticks when running headless.
+    pub frames: Option<String>,
+    /// Draw in the terminal instead of a window, see tui.rs.
+    pub tui: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OptionsError {
+    Unknown(String),
+    MissingValue(&'static str),
+    InvalidTicks(String),
+    FramesWithoutHeadless,
+    TuiAndHeadless,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsError::Unknown(arg) => write!(f, "unknown option `{}`", arg),
+            OptionsError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            OptionsError::InvalidTicks(value) => {
+                write!(f, "`{}` is not a number of ticks", value)
+            }
+            OptionsError::FramesWithoutHeadless => {
+                write!(f, "--frames only works together with --headless")
+            }
+            OptionsError::TuiAndHeadless => write!(f, "--tui and --headless don't mix"),
+        }
+    }
+}
+
+impl Options {
+    /// Parse the arguments after the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, OptionsError> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {
+                    let value = args
+                        .next()
+                        .ok_or(OptionsError::MissingValue("--headless"))?;
+                    let ticks = value
+                        .parse()
+                        .map_err(|_| OptionsError::InvalidTicks(value))?;
+                    options.headless = Some(ticks);
+                }
+                "--frames" => {
+                    let dir = args.next().ok_or(OptionsError::MissingValue("--frames"))?;
+                    options.frames = Some(dir);
+                }
+                "--tui" => options.tui = true,
+                _ => return Err(OptionsError::Unknown(arg)),
+            }
+        }
+        if options.frames.is_some() && options.headless.is_none() {
+            return Err(OptionsError::FramesWithoutHeadless);
+        }
+        if options.tui && options.headless.is_some() {
+            return Err(OptionsError::TuiAndHeadless);
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, OptionsError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+        assert_eq!(
+            parse(&["--headless", "500", "--frames", "out"]),
+            Ok(Options {
+                headless: Some(500),
+                frames: Some("out".to_owned()),
+                tui: false,
+            })
+        );
+        assert_eq!(
+            parse(&["--tui"]),
+            Ok(Options {
+                tui: true,
+                ..Options::default()
+            })
+        );
+        assert_eq!(
+            parse(&["--tui", "--headless", "10"]),
+            Err(OptionsError::TuiAndHeadless)
+        );
+        assert_eq!(
+            parse(&["--headless", "lots"]),
+            Err(OptionsError::InvalidTicks("lots".to_owned()))
+        );
+        assert_eq!(
+            parse(&["--headless"]),
+            Err(OptionsError::MissingValue("--headless"))
+        );
+        assert_eq!(
+            parse(&["--frames", "out"]),
+            Err(OptionsError::FramesWithoutHeadless)
+        );
+        assert_eq!(
+            parse(&["--fast"]),
+            Err(OptionsError::Unknown("--fast".to_owned()))
+        );
+    }
+}
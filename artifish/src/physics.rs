@@ -0,0 +1,332 @@
+use crate::environment::Environment;
+use crate::fish::{Control, Fish, PredationMode};
+use crate::vec2::Vec2;
+use crate::{BUOYANCY, COLLISION_DAMPING, COLLISION_STIFFNESS, DRAG_COEFFICIENT, FLUID_DENSITY};
+use crate::{GRAVITY, MAX_X, MAX_Y, MOVE_SPEED, PREDATION_MODE};
+
+/// What happens to fish that reach the edge of the aquarium.
+// Only the variant picked by BOUNDARY_MODE gets constructed
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// Fish are stopped at the wall, but keep their velocity.
+    Clamp,
+    /// The aquarium wraps around in both directions; distances do too.
+    Toroidal,
+    /// Fish bounce off the walls elastically.
+    Reflective,
+    /// Fish that cross a wall are removed.
+    Absorbing,
+}
+
+pub fn is_in_bounds(fish: &Fish) -> bool {
+    is_point_in_bounds(fish.x, fish.y)
+}
+
+pub fn is_point_in_bounds(x: f64, y: f64) -> bool {
+    (0.0..=MAX_X).contains(&x) && (0.0..=MAX_Y).contains(&y)
+}
+
+/// Bring a fish that moved past a wall back into the aquarium, according to
+/// the boundary mode. Absorbed fish are left outside, to be removed.
+pub fn apply_boundary(fish: &mut Fish, mode: BoundaryMode) {
+    apply_boundary_to_point(&mut fish.x, &mut fish.y, &mut fish.velocity, mode);
+}
+
+/// `apply_boundary` for anything else that moves around the aquarium.
+pub fn apply_boundary_to_point(x: &mut f64, y: &mut f64, velocity: &mut Vec2, mode: BoundaryMode) {
+    match mode {
+        BoundaryMode::Clamp => {
+            *x = x.clamp(0.0, MAX_X);
+            *y = y.clamp(0.0, MAX_Y);
+        }
+        BoundaryMode::Toroidal => {
+            *x = x.rem_euclid(MAX_X);
+            *y = y.rem_euclid(MAX_Y);
+        }
+        BoundaryMode::Reflective => {
+            let (reflected_x, flip_x) = reflect(*x, MAX_X);
+            let (reflected_y, flip_y) = reflect(*y, MAX_Y);
+            *x = reflected_x;
+            *y = reflected_y;
+            if flip_x {
+                velocity.x = -velocity.x;
+            }
+            if flip_y {
+                velocity.y = -velocity.y;
+            }
+        }
+        BoundaryMode::Absorbing => (),
+    }
+}
+
+/// Fold a coordinate back into [0, max] as if it bounced off the walls, and
+/// whether it ends up moving in the opposite direction.
+fn reflect(position: f64, max: f64) -> (f64, bool) {
+    let num_bounces = (position / max).floor() as i64;
+    let folded = position.rem_euclid(2.0 * max);
+    let position = if folded > max {
+        2.0 * max - folded
+    } else {
+        folded
+    };
+    (position, num_bounces.rem_euclid(2) == 1)
+}
+
+/// Shortest difference between two coordinates, taking wrap-around into
+/// account in toroidal mode.
+pub fn coordinate_delta(from: f64, to: f64, size: f64, mode: BoundaryMode) -> f64 {
+    let delta = to - from;
+    if mode != BoundaryMode::Toroidal {
+        return delta;
+    }
+    if delta > size / 2.0 {
+        delta - size
+    } else if delta < -size / 2.0 {
+        delta + size
+    } else {
+        delta
+    }
+}
+
+// Drag equation
+// F = 1/2 * rho * v^2 * A * Cd
+// rho = mass density of the fluid
+// v = flow velocity relative to the object
+// A = reference area of the object, typically the cross-sectional area of the object
+// Cd = drag coefficient (skin friction and form drag)
+// Wout Schellaert et al. (2010)
+
+/// Drag acceleration per unit of speed squared, i.e. |a| = k * |v|^2.
+pub fn drag_factor(fish: &Fish) -> f64 {
+    0.5 * FLUID_DENSITY * DRAG_COEFFICIENT * fish.surface_area() / fish.mass()
+}
+
+/// Slow down `velocity` by quadratic drag over `delta_time`.
+///
+/// Drag only ever changes the speed, and dv/dt = -k v^2 has the exact solution
+/// v(t) = v0 / (1 + k v0 t). Using that instead of an explicit step means drag
+/// can never overshoot and reverse the velocity, however large the time step.
+pub fn apply_drag(velocity: Vec2, drag_factor: f64, delta_time: f64) -> Vec2 {
+    velocity / (1.0 + drag_factor * velocity.length() * delta_time)
+}
+
+/// Add gravity and the push of the currents to the controls.
+///
+/// Buoyancy cancels `BUOYANCY` of the weight, so what is left makes fish sink
+/// towards the bottom of the screen. A current pushes a fish like drag would
+/// if the fish were standing still in it, so a fish left alone drifts along
+/// at the speed of the water.
+pub fn add_external_forces(fishes: &[Fish], environment: &Environment, controls: &mut [Control]) {
+    for (fish, control) in fishes.iter().zip(controls.iter_mut()) {
+        if fish.mass() <= 0.0 {
+            continue;
+        }
+        let weight = fish.mass() * GRAVITY * (1.0 - BUOYANCY);
+        control.force += Vec2::new(0.0, weight);
+
+        let current = environment.current(fish.x, fish.y);
+        control.force += current * (fish.mass() * drag_factor(fish) * current.length());
+    }
+}
+
+/// Add contact forces between overlapping fish to their controls.
+///
+/// Fish are soft bodies: overlapping circles push each other apart with a
+/// spring force proportional to the overlap, plus damping on their approach
+/// speed. Each pair gets equal and opposite forces, so momentum is conserved.
+/// Forces are scaled by the reduced mass, so a heavy fish shoves a light one
+/// aside while barely slowing down itself.
+///
+/// When fish swallow each other whole, pairs where one fish covers the
+/// other's centre are left alone, that's eating and not a collision. With
+/// bites only, nothing gets swallowed, so those pairs are pushed apart too.
+pub fn add_collision_forces(fishes: &[Fish], controls: &mut [Control]) {
+    let swallows = PREDATION_MODE != PredationMode::Bite;
+    for i in 0..fishes.len() {
+        for j in (i + 1)..fishes.len() {
+            let (a, b) = (&fishes[i], &fishes[j]);
+            if a.mass() <= 0.0 || b.mass() <= 0.0 {
+                continue;
+            }
+            if swallows && (a.covers(b) || b.covers(a)) {
+                continue;
+            }
+
+            let displacement = a.displacement_to(b);
+            let distance = displacement.length();
+            let overlap = a.radius() + b.radius() - distance;
+            if overlap <= 0.0 || distance == 0.0 {
+                continue;
+            }
+
+            let normal = displacement / distance;
+            let approach_speed = (a.velocity - b.velocity).dot(&normal);
+            let reduced_mass = a.mass() * b.mass() / (a.mass() + b.mass());
+            let magnitude =
+                (COLLISION_STIFFNESS * overlap + COLLISION_DAMPING * approach_speed) * reduced_mass;
+
+            // Contacts only push, they never pull fish together
+            if magnitude > 0.0 {
+                controls[i].force -= normal * magnitude;
+                controls[j].force += normal * magnitude;
+            }
+        }
+    }
+}
+
+/// Advance a fish by one tick under the given external force, using
+/// semi-implicit Euler: velocity is updated first, and the fish then moves
+/// with its new velocity.
+pub fn integrate(fish: &mut Fish, force: Vec2, delta_time: f64) {
+    // Starved fish are about to be removed and have no meaningful mass
+    if fish.mass() <= 0.0 {
+        return;
+    }
+
+    let impulse = force * delta_time;
+    fish.velocity += impulse / fish.mass();
+    fish.velocity = apply_drag(fish.velocity, drag_factor(fish), delta_time);
+
+    let displacement = fish.velocity * delta_time * MOVE_SPEED;
+    fish.move_by(&displacement);
+}
+
+#[cfg(test)]
+mod tests {
+    use decorum::NotNan;
+
+    use super::*;
+    use crate::lang::Program;
+
+    fn still_fish(energy: f64) -> Fish {
+        Fish::new(0, 0.0, 0.0, NotNan::from_inner(energy), Program::empty())
+    }
+
+    fn kinetic_energy(fish: &Fish) -> f64 {
+        0.5 * fish.mass() * fish.velocity.length().powi(2)
+    }
+
+    #[test]
+    fn reaches_terminal_velocity() {
+        let mut fish = still_fish(1000.0);
+        let force = Vec2::new(50.0, 0.0);
+        for _ in 0..10_000 {
+            integrate(&mut fish, force, 0.05);
+        }
+
+        // At terminal velocity drag balances the applied force
+        let terminal_speed = (force.length() / (drag_factor(&fish) * fish.mass())).sqrt();
+        let speed = fish.velocity.length();
+        assert!((speed - terminal_speed).abs() / terminal_speed < 0.01);
+        assert!(fish.velocity.x > 0.0);
+    }
+
+    #[test]
+    fn drag_never_reverses_velocity() {
+        let mut fish = still_fish(500.0);
+        fish.velocity = Vec2::new(3.0, -4.0);
+        for &delta_time in [0.01, 1.0, 1000.0].iter() {
+            let before = fish.velocity;
+            integrate(&mut fish, Vec2::zero(), delta_time);
+            assert!(fish.velocity.x > 0.0 && fish.velocity.y < 0.0);
+            assert!(fish.velocity.length() < before.length());
+        }
+    }
+
+    #[test]
+    fn drag_only_removes_kinetic_energy() {
+        let mut fish = still_fish(2000.0);
+        fish.velocity = Vec2::new(-2.0, 1.0);
+        let mut previous = kinetic_energy(&fish);
+        for _ in 0..100 {
+            integrate(&mut fish, Vec2::zero(), 0.05);
+            let current = kinetic_energy(&fish);
+            assert!(current < previous);
+            previous = current;
+        }
+        assert!(previous > 0.0);
+    }
+
+    #[test]
+    fn reflection_flips_velocity_at_walls() {
+        assert_eq!(reflect(-5.0, 100.0), (5.0, true));
+        assert_eq!(reflect(105.0, 100.0), (95.0, true));
+        assert_eq!(reflect(50.0, 100.0), (50.0, false));
+        assert_eq!(reflect(250.0, 100.0), (50.0, false));
+    }
+
+    #[test]
+    fn toroidal_distances_wrap_around() {
+        let delta = coordinate_delta(10.0, MAX_X - 10.0, MAX_X, BoundaryMode::Toroidal);
+        assert_eq!(delta, -20.0);
+        let delta = coordinate_delta(10.0, MAX_X - 10.0, MAX_X, BoundaryMode::Clamp);
+        assert_eq!(delta, MAX_X - 20.0);
+    }
+
+    #[test]
+    fn collisions_conserve_momentum() {
+        let mut small = still_fish(500.0);
+        let mut large = still_fish(20_000.0);
+        small.move_to(100.0, 100.0);
+        large.move_to(100.0 + small.radius() + large.radius() - 2.0, 100.0);
+        small.velocity = Vec2::new(1.0, 0.0);
+        let fishes = vec![small, large];
+        let mut controls = vec![
+            Control {
+                force: Vec2::zero()
+            };
+            2
+        ];
+
+        add_collision_forces(&fishes, &mut controls);
+
+        let total = controls[0].force + controls[1].force;
+        assert!(total.length() < 1e-9);
+        // The fish are pushed apart
+        assert!(controls[0].force.x < 0.0);
+        assert!(controls[1].force.x > 0.0);
+    }
+
+    #[test]
+    fn covered_fish_are_pushed_apart_unless_swallowed() {
+        let mut small = still_fish(500.0);
+        let mut large = still_fish(20_000.0);
+        small.move_to(100.0, 100.0);
+        large.move_to(101.0, 100.0);
+        assert!(large.covers(&small));
+        let fishes = vec![small, large];
+        let mut controls = vec![
+            Control {
+                force: Vec2::zero()
+            };
+            2
+        ];
+
+        add_collision_forces(&fishes, &mut controls);
+
+        let pushed = controls[0].force.x < 0.0 && controls[1].force.x > 0.0;
+        assert_eq!(pushed, PREDATION_MODE == PredationMode::Bite);
+    }
+
+    #[test]
+    fn drifts_along_with_the_current() {
+        let mut environment = Environment::default();
+        let water = Vec2::new(0.5, 0.0);
+        environment.currents = vec![crate::environment::Current::Uniform(water)];
+        let mut fishes = vec![still_fish(1000.0)];
+        for _ in 0..10_000 {
+            let mut controls = vec![
+                Control {
+                    force: Vec2::zero()
+                };
+                1
+            ];
+            add_external_forces(&fishes, &environment, &mut controls);
+            // Hold the fish at the same height, only the current matters here
+            controls[0].force.y = 0.0;
+            integrate(&mut fishes[0], controls[0].force, 0.05);
+        }
+        assert!((fishes[0].velocity.x - water.x).abs() < 0.01);
+    }
+}
@@ -0,0 +1,215 @@
+// Software rendering of the aquarium into images, for runs without a window
+// or GPU. Draws the same scene as `App::render`, minus the debug overlays
+// and side panel.
+
+use std::fmt;
+
+use rusttype::{point, Font, Scale};
+use tiny_skia::{
+    FillRule, Paint, PathBuilder, Pixmap, PremultipliedColorU8, Rect, Stroke, Transform,
+};
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::coloring::ColorMode;
+use crate::obstacles::Obstacle;
+use crate::state::State;
+use crate::{MAX_X, MAX_Y};
+
+#[derive(Debug)]
+pub enum RasterError {
+    Io(std::io::Error),
+    Font(String),
+    Size { width: u32, height: u32 },
+    Encode(String),
+}
+
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RasterError::Io(err) => write!(f, "could not read font: {}", err),
+            RasterError::Font(path) => write!(f, "{} is not a usable font", path),
+            RasterError::Size { width, height } => {
+                write!(f, "can't draw a {}x{} image", width, height)
+            }
+            RasterError::Encode(err) => write!(f, "could not write png: {}", err),
+        }
+    }
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let [r, g, b, a] = color.inner;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(
+        (r.clamp(0.0, 1.0) * 255.0) as u8,
+        (g.clamp(0.0, 1.0) * 255.0) as u8,
+        (b.clamp(0.0, 1.0) * 255.0) as u8,
+        (a.clamp(0.0, 1.0) * 255.0) as u8,
+    );
+    paint.anti_alias = true;
+    paint
+}
+
+pub struct Rasterizer {
+    font: Font<'static>,
+}
+
+impl Rasterizer {
+    pub fn new(font_path: &str) -> Result<Self, RasterError> {
+        let bytes = std::fs::read(font_path).map_err(RasterError::Io)?;
+        let font =
+            Font::try_from_vec(bytes).ok_or_else(|| RasterError::Font(font_path.to_owned()))?;
+        Ok(Rasterizer { font })
+    }
+
+    /// Draw what the camera sees, in an image the size of its viewport.
+    pub fn draw(
+        &self,
+        state: &State,
+        camera: &Camera,
+        color_mode: ColorMode,
+    ) -> Result<Pixmap, RasterError> {
+        let (width, height) = (camera.viewport[0] as u32, camera.viewport[1] as u32);
+        let mut pixmap = Pixmap::new(width, height).ok_or(RasterError::Size { width, height })?;
+        pixmap.fill(tiny_skia::Color::BLACK);
+
+        let zoom = camera.zoom as f32;
+        let world = Transform::from_row(
+            zoom,
+            0.0,
+            0.0,
+            zoom,
+            (camera.viewport[0] / 2.0 - camera.center.x * camera.zoom) as f32,
+            (camera.viewport[1] / 2.0 - camera.center.y * camera.zoom) as f32,
+        );
+        let pixel = Stroke {
+            width: 1.0 / zoom,
+            ..Stroke::default()
+        };
+
+        if let Some(walls) = Rect::from_xywh(0.0, 0.0, MAX_X as f32, MAX_Y as f32) {
+            let walls = PathBuilder::from_rect(walls);
+            let color = paint(Color::WHITE.darken(0.7));
+            pixmap.stroke_path(&walls, &color, &pixel, world, None);
+        }
+
+        let rock_color = paint(Color::new([0.4, 0.35, 0.3, 1.0]));
+        for obstacle in state.obstacles.iter() {
+            let rock = match obstacle {
+                Obstacle::Circle { center, radius } => {
+                    PathBuilder::from_circle(center.x as f32, center.y as f32, *radius as f32)
+                }
+                Obstacle::Polygon { vertices } => {
+                    let mut builder = PathBuilder::new();
+                    for (i, vertex) in vertices.iter().enumerate() {
+                        if i == 0 {
+                            builder.move_to(vertex.x as f32, vertex.y as f32);
+                        } else {
+                            builder.line_to(vertex.x as f32, vertex.y as f32);
+                        }
+                    }
+                    builder.close();
+                    builder.finish()
+                }
+            };
+            if let Some(rock) = rock {
+                pixmap.fill_path(&rock, &rock_color, FillRule::Winding, world, None);
+            }
+        }
+
+        let circle = |pixmap: &mut Pixmap, x: f64, y: f64, radius: f64, color: Color| {
+            if !camera.is_visible(x, y, radius) {
+                return None;
+            }
+            let path = PathBuilder::from_circle(x as f32, y as f32, radius as f32)?;
+            pixmap.fill_path(&path, &paint(color), FillRule::Winding, world, None);
+            Some(path)
+        };
+
+        for corpse in state.corpses.corpses.iter() {
+            circle(
+                &mut pixmap,
+                corpse.x,
+                corpse.y,
+                corpse.radius(),
+                corpse.color,
+            );
+        }
+
+        for particle in state.food.particles.iter() {
+            circle(&mut pixmap, particle.x, particle.y, 1.5, Color::WHITE);
+        }
+
+        let border = Stroke {
+            width: 1.0,
+            ..Stroke::default()
+        };
+        for fish in state.fishes.iter().rev() {
+            let fish_color = color_mode.fish_color(fish);
+            let fish_color_dark = fish_color.darken(0.5);
+            let cell = circle(&mut pixmap, fish.x, fish.y, fish.radius(), fish_color);
+            if let Some(cell) = cell {
+                pixmap.stroke_path(&cell, &paint(fish_color_dark), &border, world, None);
+                circle(&mut pixmap, fish.x, fish.y, 1.5, fish_color_dark);
+
+                if let Some(ref tag) = fish.tag {
+                    let position = camera.world_to_screen(fish.x, fish.y);
+                    let size = 20.0 * camera.zoom;
+                    self.text(&mut pixmap, tag, position, size, Color::WHITE);
+                }
+            }
+        }
+
+        let population = state.fishes.len().to_string();
+        let corner = [camera.viewport[0] - 100.0, camera.viewport[1] - 100.0];
+        self.text(&mut pixmap, &population, corner, 30.0, Color::RED);
+
+        Ok(pixmap)
+    }
+
+    pub fn save_png(
+        &self,
+        state: &State,
+        camera: &Camera,
+        color_mode: ColorMode,
+        path: &str,
+    ) -> Result<(), RasterError> {
+        self.draw(state, camera, color_mode)?
+            .save_png(path)
+            .map_err(|err| RasterError::Encode(err.to_string()))
+    }
+
+    /// Blend text into the image, with its baseline starting at `position`.
+    fn text(&self, pixmap: &mut Pixmap, text: &str, position: [f64; 2], size: f64, color: Color) {
+        let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+        let [r, g, b, a] = color.inner;
+        let pixels = pixmap.pixels_mut();
+        let start = point(position[0] as f32, position[1] as f32);
+        for glyph in self.font.layout(text, Scale::uniform(size as f32), start) {
+            let bounds = match glyph.pixel_bounding_box() {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            glyph.draw(|gx, gy, coverage| {
+                let x = bounds.min.x + gx as i32;
+                let y = bounds.min.y + gy as i32;
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    return;
+                }
+                let pixel = &mut pixels[(y * width + x) as usize];
+                let alpha = coverage * a;
+                let blend =
+                    |src: f32, dst: u8| (src * alpha * 255.0 + dst as f32 * (1.0 - alpha)) as u8;
+                let blended = PremultipliedColorU8::from_rgba(
+                    blend(r, pixel.red()),
+                    blend(g, pixel.green()),
+                    blend(b, pixel.blue()),
+                    blend(1.0, pixel.alpha()),
+                );
+                if let Some(blended) = blended {
+                    *pixel = blended;
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Snapshots of the aquarium to go back in time to. Going back and then
+// simulating again throws away the snapshots after that point, so there's
+// only ever one timeline. Snapshots leave out the metrics, see
+// `State::snapshot`.
+
+use std::collections::VecDeque;
+
+use crate::state::State;
+
+pub struct Rewind {
+    /// Ticks between snapshots.
+    pub interval: u64,
+    /// Maximum number of snapshots kept.
+    pub capacity: usize,
+    snapshots: VecDeque<State>,
+    /// The snapshot being looked at, or `None` when looking at the present.
+    position: Option<usize>,
+}
+
+impl Rewind {
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        Rewind {
+            interval,
+            capacity,
+            snapshots: VecDeque::new(),
+            position: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    /// Take a snapshot of the present if it's time for one.
+    pub fn record(&mut self, state: &State) {
+        if state.tick.is_multiple_of(self.interval) {
+            self.push(state.snapshot());
+        }
+    }
+
+    fn push(&mut self, state: State) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(state);
+    }
+
+    /// The snapshot before the one being looked at. The present is kept, so
+    /// going forward again ends up back where we started.
+    pub fn back(&mut self, present: &State) -> Option<State> {
+        let position = match self.position {
+            Some(0) => return None,
+            Some(position) => position - 1,
+            None => {
+                if self.snapshots.back().map(|s| s.tick) != Some(present.tick) {
+                    self.push(present.snapshot());
+                }
+                self.snapshots.len().checked_sub(2)?
+            }
+        };
+        self.position = Some(position);
+        Some(self.snapshots[position].clone())
+    }
+
+    /// The snapshot after the one being looked at.
+    pub fn forward(&mut self) -> Option<State> {
+        let position = self.position? + 1;
+        let state = self.snapshots.get(position)?.clone();
+        self.position = if position + 1 == self.snapshots.len() {
+            None
+        } else {
+            Some(position)
+        };
+        Some(state)
+    }
+
+    /// Carry on from the snapshot being looked at, forgetting the ones after
+    /// it.
+    pub fn resume(&mut self) {
+        if let Some(position) = self.position.take() {
+            self.snapshots.truncate(position + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewinds_and_resumes() {
+        let mut state = State::new(1);
+        let mut rewind = Rewind::new(2, 3);
+        for _ in 0..9 {
+            state.update(0.05);
+            rewind.record(&state);
+        }
+        // Snapshots at ticks 4, 6 and 8. Keeping the present at tick 9 pushes
+        // out the oldest one
+        assert_eq!(rewind.back(&state).unwrap().tick, 8);
+        assert_eq!(rewind.back(&state).unwrap().tick, 6);
+        assert!(rewind.back(&state).is_none());
+        assert_eq!(rewind.forward().unwrap().tick, 8);
+        assert_eq!(rewind.forward().unwrap().tick, 9);
+        assert_eq!(rewind.position(), None);
+
+        let past = rewind.back(&state).unwrap();
+        rewind.resume();
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(past.tick, 8);
+    }
+
+    fn fish_positions(state: &State) -> Vec<(u64, f64, f64)> {
+        state
+            .fishes
+            .iter()
+            .map(|fish| (fish.id, fish.x, fish.y))
+            .collect()
+    }
+
+    #[test]
+    fn replays_the_same_timeline() {
+        let mut state = State::new(2);
+        let mut rewind = Rewind::new(10, 10);
+        for _ in 0..100 {
+            state.update(0.05);
+            rewind.record(&state);
+        }
+        rewind.back(&state).unwrap();
+        let mut past = rewind.back(&state).unwrap();
+        rewind.resume();
+        assert_eq!(past.tick, 80);
+        assert!(past.metrics.samples.is_empty());
+
+        // Fishes born after the snapshot get the same ids again
+        let newest = |state: &State| state.fishes.iter().map(|fish| fish.id).max();
+        let newest_before = newest(&past);
+        for _ in 0..20 {
+            past.update(0.05);
+        }
+        assert!(newest(&past) > newest_before);
+        assert_eq!(fish_positions(&past), fish_positions(&state));
+    }
+}
@@ -1,32 +1,215 @@
+use std::cell::RefCell;
+
+use decorum::NotNan;
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 use rand_distr::{Distribution, Poisson};
 
-use crate::angels::generate_angel;
-use crate::fish::{execute_fish_action, Control, FishControl};
-use crate::lang::InterpreterState;
+use crate::angels::{generate_angel, make_angel_of_kind, AngelKind};
+use crate::corpses::{Corpse, Corpses};
+use crate::environment::Environment;
+use crate::fish::{execute_fish_action, Control, FishControl, FishIds, PredationMode};
+use crate::food::Food;
+use crate::history::History;
+use crate::lang::{InterpreterState, Program};
+use crate::ledger::EnergyLedger;
+use crate::metrics::Metrics;
+use crate::obstacles::Obstacle;
+use crate::physics;
 use crate::vec2::Vec2;
-use crate::{fish::Fish, FISH_GROWTH_FACTOR, FISH_SPLIT_AT_SIZE, MAX_X, MAX_Y};
-use crate::{generate_fish, FISH_GENERATION_RATE, MOVE_SPEED};
+use crate::{fish::Fish, BOUNDARY_MODE, FISH_GROWTH_FACTOR, FISH_SPLIT_AT_SIZE};
+use crate::{generate_fish, generate_fish_with_program, FISH_GENERATION_RATE, MAX_X, MAX_Y};
+use crate::{CORPSES, HISTORY_INTERVAL, HISTORY_LENGTH, STARVATION_ENERGY};
+use crate::{INITIAL_PROGRAM_MAX_DEPTH, INITIAL_PROGRAM_MIN_DEPTH, METRICS_INTERVAL};
+use crate::{PREDATION_MODE, STRICT_ENERGY_LEDGER, UNIFORM_GROWTH};
+
+/// What went into a fish's decision on the last tick, for debug overlays.
+#[derive(Clone, Debug)]
+pub struct DecisionTrace {
+    pub fish_id: u64,
+    /// Total force on the fish: its own, the current's and collisions.
+    pub force: Vec2,
+    /// Ids of the fishes its program sensed.
+    pub sensed: Vec<u64>,
+}
 
 #[derive(Clone)]
 pub struct State {
     pub fishes: Vec<Fish>,
+    pub food: Food,
+    pub corpses: Corpses,
+    pub environment: Environment,
+    pub obstacles: Vec<Obstacle>,
     pub rng: ChaCha20Rng,
+    pub ids: FishIds,
+    pub tick: u64,
+    pub metrics: Metrics,
+    /// Energy flows during the last tick, and summed over all ticks.
+    pub ledger: EnergyLedger,
+    pub energy_totals: EnergyLedger,
+    pub traces: Vec<DecisionTrace>,
+    pub history: History,
 }
 
 impl State {
     pub fn new(seed: u64) -> Self {
         let mut rng = ChaCha20Rng::seed_from_u64(seed);
-        let mut fishes: Vec<Fish> = (0..100).map(|_| generate_fish(&mut rng)).collect();
+        let mut ids = FishIds::default();
+        let programs = Program::ramped_half_and_half(
+            &mut rng,
+            100,
+            INITIAL_PROGRAM_MIN_DEPTH,
+            INITIAL_PROGRAM_MAX_DEPTH,
+        );
+        let mut fishes: Vec<Fish> = programs
+            .into_iter()
+            .map(|program| generate_fish_with_program(&mut rng, &mut ids, program))
+            .collect();
 
-        let angels: Vec<Fish> = (0..40).map(|_| generate_angel(&mut rng)).collect();
+        let angels: Vec<Fish> = (0..40)
+            .map(|_| generate_angel(&mut rng, &mut ids))
+            .collect();
         fishes.extend(angels);
 
-        Self { fishes, rng }
+        Self {
+            fishes,
+            food: Food::new(),
+            corpses: Corpses::new(),
+            environment: Environment::default(),
+            obstacles: Vec::new(),
+            rng,
+            ids,
+            tick: 0,
+            metrics: Metrics::new(METRICS_INTERVAL),
+            ledger: EnergyLedger::default(),
+            energy_totals: EnergyLedger::default(),
+            traces: Vec::new(),
+            history: History::new(HISTORY_INTERVAL, HISTORY_LENGTH),
+        }
+    }
+
+    /// A copy to go back to later. The metrics are left out: they cover the
+    /// whole run rather than one moment, and only ever grow.
+    pub fn snapshot(&self) -> State {
+        State {
+            fishes: self.fishes.clone(),
+            food: self.food.clone(),
+            corpses: self.corpses.clone(),
+            environment: self.environment.clone(),
+            obstacles: self.obstacles.clone(),
+            rng: self.rng.clone(),
+            ids: self.ids.clone(),
+            tick: self.tick,
+            metrics: Metrics::new(self.metrics.interval),
+            ledger: self.ledger.clone(),
+            energy_totals: self.energy_totals.clone(),
+            traces: self.traces.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    /// The fish drawn on top at a point, if any. Bigger fishes are drawn over
+    /// smaller ones.
+    pub fn fish_at(&self, x: f64, y: f64) -> Option<&Fish> {
+        self.fishes
+            .iter()
+            .find(|fish| fish.displacement_to_point(x, y).length() < fish.radius())
+    }
+
+    pub fn fish_mut(&mut self, id: u64) -> Option<&mut Fish> {
+        self.fishes.iter_mut().find(|fish| fish.id == id)
+    }
+
+    // God tools. These change the aquarium between ticks, so the energy they
+    // add or take away shows up in the totals but not in the ledger.
+
+    /// Add an angel at a point. Returns its id.
+    pub fn spawn_angel(&mut self, kind: AngelKind, x: f64, y: f64) -> u64 {
+        let mut fish = make_angel_of_kind(&mut self.rng, &mut self.ids, kind);
+        fish.move_to(x, y);
+        self.add_fish(fish)
+    }
+
+    /// Add a fish with a random program at a point. Returns its id.
+    pub fn spawn_random_fish(&mut self, x: f64, y: f64) -> u64 {
+        let mut fish = generate_fish(&mut self.rng, &mut self.ids);
+        fish.move_to(x, y);
+        self.add_fish(fish)
+    }
+
+    /// Add a copy of a fish next to it, with its own id. Returns the new id.
+    pub fn clone_fish(&mut self, id: u64) -> Option<u64> {
+        let mut fish = self.fishes.iter().find(|fish| fish.id == id)?.clone();
+        fish.id = self.ids.take();
+        fish.age = 0;
+        fish.move_by(&Vec2::new(fish.radius() * 2.0, 0.0));
+        physics::apply_boundary(&mut fish, BOUNDARY_MODE);
+        Some(self.add_fish(fish))
+    }
+
+    fn add_fish(&mut self, fish: Fish) -> u64 {
+        let id = fish.id;
+        self.fishes.push(fish);
+        id
+    }
+
+    /// Put a fish somewhere else, at a standstill. Returns whether it exists.
+    pub fn move_fish(&mut self, id: u64, x: f64, y: f64) -> bool {
+        match self.fish_mut(id) {
+            Some(fish) => {
+                fish.move_to(x.clamp(0.0, MAX_X), y.clamp(0.0, MAX_Y));
+                fish.velocity = Vec2::zero();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_fish(&mut self, id: u64) -> Option<Fish> {
+        let index = self.fishes.iter().position(|fish| fish.id == id)?;
+        Some(self.fishes.remove(index))
+    }
+
+    /// Add energy to a fish, or take it away if `amount` is negative. Taking
+    /// energy away stops at the starvation threshold, which leaves the fish
+    /// alive; it starves once it uses up more energy than it grows. Returns
+    /// whether it exists.
+    pub fn give_energy(&mut self, id: u64, amount: f64) -> bool {
+        match self.fish_mut(id) {
+            Some(fish) => {
+                let energy = (fish.energy.into_inner() + amount).max(STARVATION_ENERGY);
+                fish.energy = NotNan::from_inner(energy);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns whether the fish exists.
+    pub fn set_tag(&mut self, id: u64, tag: Option<String>) -> bool {
+        match self.fish_mut(id) {
+            Some(fish) => {
+                fish.tag = tag;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// All energy in the aquarium, in fishes, food and corpses.
+    pub fn total_energy(&self) -> f64 {
+        let in_fishes: f64 = self.fishes.iter().map(|f| f.energy.into_inner()).sum();
+        in_fishes + self.food.total_energy() + self.corpses.total_energy()
     }
 
     pub fn update(&mut self, delta_time: f64) {
+        self.metrics
+            .record(self.tick, &self.fishes, &self.energy_totals);
+        self.tick += 1;
+        let total_before = self.total_energy();
+        let mut ledger = EnergyLedger::default();
+        self.environment.advance(delta_time);
+
         let mut controls = vec![
             Control {
                 force: Vec2::zero()
@@ -36,22 +219,48 @@ impl State {
         let mut fish_control = FishControl {
             controls: &mut controls,
             fishes: &mut self.fishes,
+            environment: &self.environment,
+            ledger: &mut ledger,
+            ids: &mut self.ids,
         };
 
         for fish in fish_control.fishes.iter_mut() {
-            fish.energy += FISH_GROWTH_FACTOR * fish.surface_area() * delta_time;
+            fish.age += 1;
+        }
+
+        if UNIFORM_GROWTH {
+            for fish in fish_control.fishes.iter_mut() {
+                let growth = self.environment.growth_factor(fish.x, fish.y);
+                let growth = FISH_GROWTH_FACTOR * growth * fish.surface_area() * delta_time;
+                fish.energy += growth;
+                fish_control.ledger.growth += growth;
+            }
         }
 
         // behave fishes
+        let mut sensed = Vec::with_capacity(fish_control.fishes.len());
         for i in 0..fish_control.fishes.len() {
             let action = {
                 let interpreter_state = InterpreterState {
                     fishes: fish_control.fishes,
+                    food: &self.food,
+                    environment: &self.environment,
+                    obstacles: &self.obstacles,
                     fish_num: i,
+                    sensed: RefCell::new(Vec::new()),
                 };
 
-                fish_control.fishes[i].program.run(&interpreter_state)
+                let action = fish_control.fishes[i].program.run(&interpreter_state);
+                let sensed_ids = interpreter_state
+                    .sensed
+                    .into_inner()
+                    .into_iter()
+                    .map(|j| fish_control.fishes[j].id)
+                    .collect::<Vec<_>>();
+                sensed.push(sensed_ids);
+                action
             };
+            fish_control.fishes[i].last_action = action;
             execute_fish_action(&mut fish_control, i, action, delta_time, &mut self.rng);
         }
 
@@ -62,35 +271,31 @@ impl State {
             }
         }
 
-        // Move fishes
-        for fish in self.fishes.iter_mut() {
-            let displacement = fish.velocity * delta_time * MOVE_SPEED;
-            fish.move_by(&displacement);
-            fish.move_to(fish.x.clamp(0.0, MAX_X), fish.y.clamp(0.0, MAX_Y));
-        }
-
-        // TOTO
-        for (fish, control) in self.fishes.iter_mut().zip(controls.iter()) {
-            // Drag equation
-            // F = 1/2 * rho * v^2 * A * Cd
-            // rho = mass density of the fluid
-            // v = flow velocity relative to the object
-            // A = reference area of the object, typically the cross-sectional area of the object
-            // Cd = drag coefficient (skin friction and form drag)
-            // Wout Schellaert et al. (2010)
-            // OUR_DRAG_COEF = 1/2 * rho * Cd
-
-            const OUR_DRAG_COEF: f64 = 0.0; // 1/2 * mass density of fluid * drag coefficient
-            let drag_force: Vec2 =
-                OUR_DRAG_COEF * fish.velocity.invert().powi(2) * fish.surface_area();
+        physics::add_external_forces(&self.fishes, &self.environment, &mut controls);
+        physics::add_collision_forces(&self.fishes, &mut controls);
 
-            debug_assert!(drag_force.length() * delta_time <= fish.momentum().length());
+        // Newborns didn't decide anything yet
+        sensed.resize(self.fishes.len(), Vec::new());
+        self.traces = self
+            .fishes
+            .iter()
+            .zip(controls.iter())
+            .zip(sensed)
+            .map(|((fish, control), sensed)| DecisionTrace {
+                fish_id: fish.id,
+                force: control.force,
+                sensed,
+            })
+            .collect();
 
-            // Apply controls
-            let resultant_force = control.force + drag_force;
-            let impulse = resultant_force * delta_time;
-            let delta_velocity = impulse / fish.mass();
-            fish.velocity += delta_velocity;
+        // Apply controls and drag, then move fishes
+        for (fish, control) in self.fishes.iter_mut().zip(controls.iter()) {
+            physics::integrate(fish, control.force, delta_time);
+            // Obstacles first, they can push a fish through a wall
+            for obstacle in self.obstacles.iter() {
+                obstacle.collide(fish);
+            }
+            physics::apply_boundary(fish, BOUNDARY_MODE);
         }
 
         // Generate new fishes
@@ -98,40 +303,202 @@ impl State {
         let distr = Poisson::new(FISH_GENERATION_RATE * delta_time).unwrap();
         let n_fishes: u32 = distr.sample(&mut self.rng).floor() as u32;
         for _ in 0..n_fishes {
-            self.fishes.push(generate_fish(&mut self.rng))
+            let fish = generate_fish(&mut self.rng, &mut self.ids);
+            ledger.spawned_fish += fish.mass();
+            self.fishes.push(fish)
         }
 
         // prevent aquarium leaks
-        let mut i = 0;
-        while i < self.fishes.len() {
-            let fish = &self.fishes[i];
-
-            if fish.x > MAX_X || fish.y > MAX_Y || fish.x < 0.0 || fish.y < 0.0 {
-                self.fishes.remove(i);
-            } else {
-                i += 1;
+        self.fishes.retain(|f| {
+            let in_bounds = physics::is_in_bounds(f);
+            if !in_bounds {
+                ledger.leaked += f.mass();
             }
+            in_bounds
+        });
+
+        for fish in self.fishes.iter_mut() {
+            fish.peak_energy = fish.peak_energy.max(fish.energy);
         }
 
         // WHEN ANGELS DESERVE TO DIEEEEEEEEEEEEEEE
-        self.fishes.retain(|f| f.energy > 0.0);
+        let corpses = &mut self.corpses;
+        self.fishes.retain(|f| {
+            let alive = f.energy > STARVATION_ENERGY;
+            if !alive {
+                if CORPSES {
+                    let corpse = Corpse::from_fish(f);
+                    // What energy is left moves over, the body comes on top
+                    ledger.died += f.mass();
+                    ledger.remains += corpse.energy.into_inner() - f.mass();
+                    corpses.add(corpse);
+                } else {
+                    ledger.starved += f.mass();
+                }
+            }
+            alive
+        });
 
         let fishes = &mut self.fishes;
         fishes.sort_by_key(|f| -f.energy);
 
         // Fishes eat other fishes
         let mut i = 0;
-        while i < fishes.len() {
+        while PREDATION_MODE != PredationMode::Bite && i < fishes.len() {
             let mut j = i + 1;
             while j < fishes.len() {
                 if fishes[i].covers(&fishes[j]) {
                     let eaten = fishes.remove(j);
                     fishes[i].eat(&eaten);
+                    ledger.eaten += eaten.mass();
                 } else {
                     j += 1;
                 }
             }
             i += 1;
         }
+
+        ledger.spawned_food += self
+            .food
+            .spawn(&mut self.rng, &self.environment, delta_time);
+        ledger.food_eaten += self.food.feed(fishes);
+
+        self.corpses
+            .update(&self.environment, &mut self.food, &mut ledger, delta_time);
+        ledger.scavenged += self.corpses.feed(&mut self.fishes);
+
+        let total_after = self.total_energy();
+        if STRICT_ENERGY_LEDGER {
+            ledger.check_balance(total_before, total_after);
+        }
+        self.history.record(self.tick, &self.fishes, total_after);
+        self.energy_totals += &ledger;
+        self.ledger = ledger;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angels::AngelKind;
+    use crate::lang::expressions::{BiteExpr, ConstExpr, DichtsteVisExpr};
+    use crate::lang::{ExprSlot, Fraction};
+    use crate::physics::BoundaryMode;
+
+    fn biter(id: u64, x: f64, y: f64, energy: f64) -> Fish {
+        let program = Program {
+            root: ExprSlot::new(Box::new(BiteExpr {
+                target: ExprSlot::new(Box::new(DichtsteVisExpr)),
+                size: ExprSlot::new(Box::new(ConstExpr::new(Fraction::from_f64(1.0)))),
+            })),
+        };
+        Fish::new(id, x, y, NotNan::from_inner(energy), program)
+    }
+
+    #[test]
+    fn books_balance_with_bites_and_corpses() {
+        let mut state = State::new(0);
+        state.fishes = vec![
+            biter(0, 200.0, 200.0, 2000.0),
+            biter(1, 215.0, 200.0, 50.0),
+            biter(2, 200.0, 215.0, 50.0),
+        ];
+        for _ in 0..200 {
+            let total_before = state.total_energy();
+            state.update(0.05);
+            state
+                .ledger
+                .check_balance(total_before, state.total_energy());
+        }
+        if PREDATION_MODE != PredationMode::Cover {
+            assert!(state.energy_totals.bitten > 0.0);
+        }
+        if CORPSES {
+            assert!(state.energy_totals.died > 0.0);
+        }
+    }
+
+    #[test]
+    fn dead_fishes_leave_their_body() {
+        let mut state = State::new(0);
+        let mut fish = biter(0, 200.0, 200.0, 1.0);
+        fish.peak_energy = NotNan::from_inner(1000.0);
+        state.fishes = vec![fish];
+        let total_before = state.total_energy();
+        state.update(0.05);
+        state
+            .ledger
+            .check_balance(total_before, state.total_energy());
+        if CORPSES {
+            assert!(state.fish_mut(0).is_none());
+            assert_eq!(state.corpses.corpses.len(), 1);
+            assert!(state.corpses.total_energy() > 400.0);
+        }
+    }
+
+    #[test]
+    fn obstacles_against_a_wall_keep_fishes_in() {
+        let mut state = State::new(0);
+        state.obstacles = vec![Obstacle::Polygon {
+            vertices: vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(360.0, 0.0),
+                Vec2::new(180.0, 180.0),
+            ],
+        }];
+        // Closest to the top edge, so the obstacle pushes it out through the wall
+        state.fishes = vec![biter(0, 180.0, 10.0, 500.0)];
+        state.update(0.05);
+        if BOUNDARY_MODE != BoundaryMode::Absorbing {
+            assert!(state.fish_mut(0).is_some());
+            assert_eq!(state.energy_totals.leaked, 0.0);
+        }
+    }
+
+    #[test]
+    fn god_tools_change_one_fish() {
+        let mut state = State::new(0);
+        state.fishes.clear();
+        let id = state.spawn_angel(AngelKind::Smartie, 100.0, 200.0);
+        let fish = state.fish_mut(id).unwrap();
+        assert_eq!((fish.x, fish.y), (100.0, 200.0));
+        fish.age = 50;
+        fish.velocity = Vec2::new(3.0, 4.0);
+
+        let clone_id = state.clone_fish(id).unwrap();
+        assert_ne!(clone_id, id);
+        assert_eq!(state.fish_mut(clone_id).unwrap().age, 0);
+        assert_eq!(state.fish_mut(id).unwrap().age, 50);
+        assert_eq!(state.fishes.len(), 2);
+
+        assert!(state.move_fish(id, -50.0, MAX_Y + 50.0));
+        let fish = state.fish_mut(id).unwrap();
+        assert_eq!((fish.x, fish.y), (0.0, MAX_Y));
+        assert_eq!(fish.velocity.length(), 0.0);
+
+        let energy = state.fish_mut(id).unwrap().energy.into_inner();
+        assert!(state.give_energy(id, 10.0));
+        assert_eq!(
+            state.fish_mut(id).unwrap().energy.into_inner(),
+            energy + 10.0
+        );
+        assert!(state.give_energy(id, -1e9));
+        assert_eq!(
+            state.fish_mut(id).unwrap().energy.into_inner(),
+            STARVATION_ENERGY
+        );
+
+        assert!(state.set_tag(id, Some("adam".to_owned())));
+        assert_eq!(state.fish_mut(id).unwrap().tag.as_deref(), Some("adam"));
+        assert!(state.set_tag(id, None));
+        assert_eq!(state.fish_mut(id).unwrap().tag, None);
+
+        assert_eq!(state.remove_fish(id).map(|fish| fish.id), Some(id));
+        assert!(state.remove_fish(id).is_none());
+        assert!(state.clone_fish(id).is_none());
+        assert!(!state.move_fish(id, 0.0, 0.0));
+        assert!(!state.give_energy(id, 1.0));
+        assert!(!state.set_tag(id, None));
+        assert_eq!(state.fishes.len(), 1);
     }
 }
@@ -0,0 +1,220 @@
+// A front-end for the terminal, to watch runs over SSH. Draws the aquarium
+// with braille dots next to the population numbers.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::Color as TermColor;
+use ratatui::symbols::Marker;
+use ratatui::text::Line as TextLine;
+use ratatui::widgets::canvas::{Canvas, Circle, Line, Points};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::clock::{Clock, TIME_STEP};
+use crate::color::Color;
+use crate::obstacles::Obstacle;
+use crate::state::State;
+use crate::{MAX_SPEED, MAX_X, MAX_Y, MIN_SPEED};
+
+// Redraw this often, in seconds
+const FRAME_TIME: f64 = 0.1;
+const STATS_WIDTH: u16 = 32;
+
+fn term_color(color: Color) -> TermColor {
+    let [r, g, b, _] = color.inner;
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0) as u8;
+    TermColor::Rgb(channel(r), channel(g), channel(b))
+}
+
+struct Tui<'a> {
+    state: &'a mut State,
+    paused: bool,
+    speed: f64,
+    clock: Clock,
+}
+
+/// Run the simulation in the terminal until Q or Esc is pressed.
+pub fn run(state: &mut State) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut tui = Tui {
+        state,
+        paused: false,
+        speed: 1.0,
+        clock: Clock::new(),
+    };
+    let result = tui.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl<'a> Tui<'a> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut last_frame = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            // Handle keys until it's time for the next frame
+            let deadline = last_frame + Duration::from_secs_f64(FRAME_TIME);
+            while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+                if !event::poll(timeout)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.press(key.code) {
+                        return Ok(());
+                    }
+                }
+            }
+
+            let dt = last_frame.elapsed().as_secs_f64();
+            last_frame = Instant::now();
+            self.update(dt);
+        }
+    }
+
+    /// Same fixed time step as the window's front-end.
+    fn update(&mut self, dt: f64) {
+        if self.paused {
+            self.clock.reset();
+        } else {
+            for _ in 0..self.clock.ticks_due(dt, self.speed) {
+                self.tick();
+            }
+        }
+        self.clock.update_tps();
+    }
+
+    fn tick(&mut self) {
+        self.state.update(TIME_STEP);
+        self.clock.count_tick();
+    }
+
+    /// Space pauses, N steps one tick while paused, [ and ] change the speed,
+    /// Q or Esc quits. Returns whether to keep running.
+    fn press(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('n') if self.paused => self.tick(),
+            KeyCode::Char('[') => self.speed = (self.speed / 2.0).max(MIN_SPEED),
+            KeyCode::Char(']') => self.speed = (self.speed * 2.0).min(MAX_SPEED),
+            _ => (),
+        }
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [aquarium, stats] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(STATS_WIDTH)])
+                .areas(frame.area());
+        frame.render_widget(self.aquarium(), aquarium);
+        frame.render_widget(self.stats(), stats);
+    }
+
+    fn aquarium(&self) -> Canvas<'_, impl Fn(&mut ratatui::widgets::canvas::Context) + '_> {
+        let state = &*self.state;
+        // The canvas has y going up, the aquarium has it going down
+        let flip = |y: f64| MAX_Y - y;
+        Canvas::default()
+            .block(Block::bordered().title(" artifish "))
+            .marker(Marker::Braille)
+            .x_bounds([0.0, MAX_X])
+            .y_bounds([0.0, MAX_Y])
+            .paint(move |ctx| {
+                let rock = term_color(Color::new([0.4, 0.35, 0.3, 1.0]));
+                for obstacle in state.obstacles.iter() {
+                    match obstacle {
+                        Obstacle::Circle { center, radius } => ctx.draw(&Circle {
+                            x: center.x,
+                            y: flip(center.y),
+                            radius: *radius,
+                            color: rock,
+                        }),
+                        Obstacle::Polygon { vertices } => {
+                            let next = vertices.iter().cycle().skip(1);
+                            for (a, b) in vertices.iter().zip(next) {
+                                ctx.draw(&Line {
+                                    x1: a.x,
+                                    y1: flip(a.y),
+                                    x2: b.x,
+                                    y2: flip(b.y),
+                                    color: rock,
+                                });
+                            }
+                        }
+                    }
+                }
+
+                let food: Vec<(f64, f64)> = state
+                    .food
+                    .particles
+                    .iter()
+                    .map(|particle| (particle.x, flip(particle.y)))
+                    .collect();
+                ctx.draw(&Points {
+                    coords: &food,
+                    color: TermColor::White,
+                });
+
+                for corpse in state.corpses.corpses.iter() {
+                    ctx.draw(&Points {
+                        coords: &[(corpse.x, flip(corpse.y))],
+                        color: term_color(corpse.color),
+                    });
+                }
+
+                for fish in state.fishes.iter().rev() {
+                    let color = term_color(fish.color);
+                    ctx.draw(&Circle {
+                        x: fish.x,
+                        y: flip(fish.y),
+                        radius: fish.radius(),
+                        color,
+                    });
+                    // Small fishes are smaller than a dot
+                    ctx.draw(&Points {
+                        coords: &[(fish.x, flip(fish.y))],
+                        color,
+                    });
+                }
+            })
+    }
+
+    fn stats(&self) -> Paragraph<'_> {
+        let state = &*self.state;
+        let mode = if self.paused {
+            "paused".to_owned()
+        } else {
+            format!("x{}", self.speed)
+        };
+        let mut tags = BTreeMap::new();
+        for tag in state.fishes.iter().filter_map(|fish| fish.tag.as_ref()) {
+            *tags.entry(tag.as_str()).or_insert(0) += 1;
+        }
+
+        let mut lines = vec![
+            format!("tick {}", state.tick),
+            format!("{:.1} ticks/s  {}", self.clock.tps(), mode),
+            String::new(),
+            format!("fishes: {}", state.fishes.len()),
+            format!("food particles: {}", state.food.particles.len()),
+            format!("corpses: {}", state.corpses.corpses.len()),
+            format!("total energy: {:.0}", state.total_energy()),
+            format!("last tick: {:+.1}", state.ledger.net()),
+            String::new(),
+        ];
+        lines.extend(tags.iter().map(|(tag, n)| format!("{}: {}", tag, n)));
+        lines.extend(vec![
+            String::new(),
+            "space pause, n step".to_owned(),
+            "[ ] speed, q quit".to_owned(),
+        ]);
+
+        let lines: Vec<TextLine> = lines.into_iter().map(TextLine::from).collect();
+        Paragraph::new(lines).block(Block::bordered().title(" stats "))
+    }
+}
@@ -9,6 +9,12 @@ pub struct Vec2 {
     pub y: f64,
 }
 
+/// A velocity computed by a program. Programs keep these apart from unit
+/// directions, which are plain `Vec2`s, so mutations can tell which grammar
+/// symbol a vector slot holds.
+#[derive(Clone, Copy, Debug)]
+pub struct Velocity(pub Vec2);
+
 impl Vec2 {
     pub fn new(x: f64, y: f64) -> Self {
         Vec2 { x, y }
@@ -27,8 +33,8 @@ impl Vec2 {
         }
     }
 
-    pub fn powi(self, n: i32) -> Self {
-        self.normalized() * self.length().powi(n)
+    pub fn dot(&self, other: &Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
     }
 
     pub fn length(&self) -> f64 {
@@ -38,7 +44,7 @@ impl Vec2 {
     pub fn normalized(&self) -> Self {
         let length = self.length();
         if length == 0.0 {
-            return Vec2::zero();
+            Vec2::zero()
         } else {
             Vec2 {
                 x: self.x / length,
@@ -46,13 +52,6 @@ impl Vec2 {
             }
         }
     }
-
-    pub fn invert(&self) -> Vec2 {
-        Vec2 {
-            x: -self.x,
-            y: -self.y,
-        }
-    }
 }
 
 impl std::ops::Div<f64> for Vec2 {
@@ -97,19 +96,20 @@ impl std::ops::Mul<Vec2> for f64 {
     }
 }
 
+// Scaling a direction by a fraction is how programs make velocities
 impl std::ops::Mul<Vec2> for Fraction {
-    type Output = Vec2;
+    type Output = Velocity;
 
     fn mul(self, rhs: Vec2) -> Self::Output {
-        rhs * self.to_f64()
+        Velocity(rhs * self.to_f64())
     }
 }
 
 impl std::ops::Mul<Fraction> for Vec2 {
-    type Output = Vec2;
+    type Output = Velocity;
 
     fn mul(self, rhs: Fraction) -> Self::Output {
-        self * rhs.to_f64()
+        Velocity(self * rhs.to_f64())
     }
 }
 
@@ -1,63 +1,258 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Ident};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, DeriveInput, Error, Field, Ident, Index, Lit, Member, Meta, NestedMeta,
+    Result, Type, WherePredicate,
+};
+
+/// Options set on the struct itself, e.g.
+/// ```text
+/// #[expr_tree_node(name = "Split", output = "Action", mutate)]
+/// struct SplitExpr { ... }
+/// ```
+/// - `name`: node name shown when printing programs, defaults to the struct
+///   name without its `Expr` suffix.
+/// - `output`: the type the expression evaluates to, used in the signature
+///   and required for `mutate`.
+/// - `mutate`: also derive the default `Mutable` impl, which either wraps the
+///   node in a generic expression or mutates one child and clones the rest.
+/// - `regenerate`: instead derive a `Mutable` impl that either wraps the node
+///   in a generic expression or replaces it with a new minimal one, for nodes
+///   without anything worth mutating. `regenerate = "generate_fn"` builds the
+///   replacement with `generate_fn(rng)` instead of `generate_minimal_expr`.
+/// - `bound`: extra where clause predicates for the generated `Mutable` impl.
+#[derive(Default)]
+struct NodeOptions {
+    name: Option<String>,
+    output: Option<Type>,
+    mutate: Option<syn::Path>,
+    regenerate: Option<(syn::Path, syn::Path)>,
+    bounds: Vec<WherePredicate>,
+}
+
+fn parse_node_options(attrs: &[syn::Attribute]) -> Result<NodeOptions> {
+    let mut options = NodeOptions::default();
+
+    for attribute in attrs.iter() {
+        if !attribute.path.is_ident("expr_tree_node") {
+            continue;
+        }
+
+        let items = match attribute.parse_meta()? {
+            Meta::List(list) => list.nested,
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "expected #[expr_tree_node(...)] with a list of options",
+                ))
+            }
+        };
+
+        for item in items.iter() {
+            match item {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("mutate") => {
+                    options.mutate = Some(path.clone());
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("regenerate") => {
+                    options.regenerate =
+                        Some((path.clone(), syn::parse_quote!(generate_minimal_expr)));
+                }
+                NestedMeta::Meta(Meta::NameValue(pair)) => {
+                    let value = match &pair.lit {
+                        Lit::Str(value) => value,
+                        lit => return Err(Error::new_spanned(lit, "expected a string literal")),
+                    };
+                    if pair.path.is_ident("name") {
+                        options.name = Some(value.value());
+                    } else if pair.path.is_ident("output") {
+                        options.output = Some(value.parse()?);
+                    } else if pair.path.is_ident("regenerate") {
+                        options.regenerate = Some((pair.path.clone(), value.parse()?));
+                    } else if pair.path.is_ident("bound") {
+                        let where_clause: syn::WhereClause =
+                            syn::parse_str(&format!("where {}", value.value()))
+                                .map_err(|err| Error::new_spanned(value, err))?;
+                        options.bounds.extend(where_clause.predicates);
+                    } else {
+                        return Err(Error::new_spanned(
+                            &pair.path,
+                            "unknown option, expected one of `name`, `output`, `regenerate`, `bound`",
+                        ));
+                    }
+                }
+                item => {
+                    return Err(Error::new_spanned(
+                        item,
+                        "unknown option, expected one of `name`, `output`, `bound`, `mutate`, \
+                         `regenerate`",
+                    ))
+                }
+            }
+        }
+    }
+
+    if let (Some(_), Some((regenerate, _))) = (&options.mutate, &options.regenerate) {
+        return Err(Error::new_spanned(
+            regenerate,
+            "`mutate` and `regenerate` can't be combined",
+        ));
+    }
+
+    Ok(options)
+}
+
+/// You can set an attribute on an Expr struct field that makes us
+/// consider this field not a child expression, e.g.
+/// ```text
+/// struct ConstExpr<T> {
+///     #[expr_tree_node(not_a_child)]
+///     value: T
+/// }
+/// ```
+fn is_child(field: &Field) -> Result<bool> {
+    let mut is_child = true;
+    for attribute in field.attrs.iter() {
+        if !attribute.path.is_ident("expr_tree_node") {
+            continue;
+        }
+
+        match attribute.parse_args::<Ident>() {
+            Ok(ident) if ident == "not_a_child" => {
+                is_child = false;
+            }
+            Ok(ident) => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "unknown field option, expected `not_a_child`",
+                ))
+            }
+            Err(_) => {
+                return Err(Error::new_spanned(
+                    attribute,
+                    "expected #[expr_tree_node(not_a_child)]",
+                ))
+            }
+        }
+    }
+    Ok(is_child)
+}
+
+/// How to get at a field: by name, or by position for tuple structs.
+fn field_member(index: usize, field: &Field) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index {
+            index: index as u32,
+            span: field.span(),
+        }),
+    }
+}
+
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// The type inside an `ExprSlot<...>`, or the field type itself if it isn't
+/// a slot.
+fn slot_inner_type(ty: &Type) -> &Type {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == "ExprSlot" {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                        return inner;
+                    }
+                }
+            }
+        }
+    }
+    ty
+}
+
+fn type_to_string(ty: &Type) -> String {
+    ty.to_token_stream()
+        .to_string()
+        .replace(" :: ", "::")
+        .replace(":: ", "::")
+        .replace(" <", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" ,", ",")
+}
 
 #[proc_macro_derive(ArtifishExpr, attributes(expr_tree_node))]
 pub fn derive_artifish_expr(input: TokenStream) -> TokenStream {
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
 
+    expand_artifish_expr(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_artifish_expr(input: DeriveInput) -> Result<TokenStream2> {
+    let options = parse_node_options(&input.attrs)?;
+
     let data = match input.data {
         syn::Data::Struct(data) => data,
-        _ => panic!("expected a struct"),
+        syn::Data::Enum(data) => {
+            return Err(Error::new_spanned(
+                data.enum_token,
+                "ArtifishExpr can only be derived for structs",
+            ))
+        }
+        syn::Data::Union(data) => {
+            return Err(Error::new_spanned(
+                data.union_token,
+                "ArtifishExpr can only be derived for structs",
+            ))
+        }
     };
 
     let struct_ident = input.ident;
-    let struct_generics = input.generics;
+    let original_generics = input.generics;
+    let mut struct_generics = original_generics.clone();
 
-    let mut child_exprs = Vec::new();
-
-    for field in data.fields.iter() {
-        // You can set an attribute on an Expr struct field that makes us
-        // consider this field not a child expression, e.g.
-        // ```
-        // struct ConstExpr<T> {
-        //     #[expr_tree_node(not_a_child)]
-        //     value: T
-        // }
-        // ```
-        let mut skip_field = false;
-        for attribute in field.attrs.iter() {
-            if !attribute.path.is_ident("expr_tree_node") {
-                continue;
-            }
-
-            match attribute.parse_args::<Ident>() {
-                Ok(ident) if ident == "not_a_child" => {
-                    skip_field = true;
-                }
-                Ok(ident) => panic!("Shit input {}", ident.to_string()),
-                Err(_) => panic!("Shit input {:?}", attribute.tokens),
-            }
-        }
+    let fields: Vec<(Member, &Field)> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| (field_member(i, field), field))
+        .collect();
 
-        if !skip_field {
-            child_exprs.push(field);
+    let mut child_exprs = Vec::new();
+    for (member, field) in fields.iter() {
+        if is_child(field)? {
+            child_exprs.push((member, *field));
         }
     }
 
     let num_children = child_exprs.len() as u64;
 
+    // Child slots are handed out as `dyn MutableExprSlot`, so require that of
+    // every child field type.
+    let where_clause = struct_generics.make_where_clause();
+    for (_, field) in child_exprs.iter() {
+        let ty = &field.ty;
+        where_clause
+            .predicates
+            .push(syn::parse_quote! { #ty: MutableExprSlot });
+    }
+    let (impl_generics, ty_generics, where_clause) = struct_generics.split_for_impl();
+
     let child_match_entries = child_exprs
         .iter()
         .enumerate()
-        .map(|(i, field)| {
+        .map(|(i, (member, _))| {
             let i = i as u64;
-            // TODO: no unwrap
-            let ident = field.ident.as_ref().unwrap();
             quote! {
-                #i => &self.#ident,
+                #i => &self.#member,
             }
         })
         .collect::<Vec<_>>();
@@ -65,18 +260,52 @@ pub fn derive_artifish_expr(input: TokenStream) -> TokenStream {
     let child_match_entries_mut = child_exprs
         .iter()
         .enumerate()
-        .map(|(i, field)| {
+        .map(|(i, (member, _))| {
             let i = i as u64;
-            // TODO: no unwrap
-            let ident = field.ident.as_ref().unwrap();
             quote! {
-                #i => &mut self.#ident,
+                #i => &mut self.#member,
             }
         })
         .collect::<Vec<_>>();
 
-    let tokens = quote! {
-        impl #struct_generics ExprTreeNode for #struct_ident #struct_generics {
+    let child_name_entries = child_exprs
+        .iter()
+        .enumerate()
+        .map(|(i, (member, _))| {
+            let i = i as u64;
+            let name = member_name(member);
+            quote! {
+                #i => #name,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let struct_name = struct_ident.to_string();
+    let node_name = options.name.clone().unwrap_or_else(|| {
+        struct_name
+            .strip_suffix("Expr")
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&struct_name)
+            .to_owned()
+    });
+
+    let slot_signatures = child_exprs
+        .iter()
+        .map(|(member, field)| {
+            format!(
+                "{}: {}",
+                member_name(member),
+                type_to_string(slot_inner_type(&field.ty))
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut signature = format!("{}({})", node_name, slot_signatures.join(", "));
+    if let Some(output) = &options.output {
+        signature += &format!(" -> {}", type_to_string(output));
+    }
+
+    let mut tokens = quote! {
+        impl #impl_generics ExprTreeNode for #struct_ident #ty_generics #where_clause {
             fn num_children(&self) -> u64 {
                 #num_children
             }
@@ -94,8 +323,76 @@ pub fn derive_artifish_expr(input: TokenStream) -> TokenStream {
                     _ => panic!("child index out of range"),
                 }
             }
+
+            fn node_name(&self) -> &'static str {
+                #node_name
+            }
+
+            fn child_name(&self, n: u64) -> &'static str {
+                match n {
+                    #( #child_name_entries )*
+                    _ => panic!("child index out of range"),
+                }
+            }
+
+            fn signature(&self) -> &'static str {
+                #signature
+            }
         }
     };
 
-    return tokens.into();
+    let mutable_option = options
+        .mutate
+        .as_ref()
+        .or(options.regenerate.as_ref().map(|(path, _)| path));
+    if let Some(option) = mutable_option {
+        let output = options.output.as_ref().ok_or_else(|| {
+            let name = option.to_token_stream().to_string();
+            Error::new_spanned(
+                option,
+                format!("`{}` needs the output type, add `output = \"...\"`", name),
+            )
+        })?;
+
+        let branches = match &options.regenerate {
+            // Replace the whole node
+            Some((_, generator)) => vec![quote! { #generator(rng), }],
+            // One branch per child: mutate that child, clone all other fields
+            None => child_exprs
+                .iter()
+                .map(|(mutated, _)| {
+                    let field_inits = fields.iter().map(|(member, _)| {
+                        if member == *mutated {
+                            quote! { #member: self.#member.mutate(rng) }
+                        } else {
+                            quote! { #member: self.#member.clone() }
+                        }
+                    });
+                    quote! {
+                        Box::new(#struct_ident { #( #field_inits ),* }),
+                    }
+                })
+                .collect::<Vec<_>>(),
+        };
+
+        let mut mutable_generics = original_generics.clone();
+        mutable_generics
+            .make_where_clause()
+            .predicates
+            .extend(options.bounds.iter().cloned());
+        let (impl_generics, ty_generics, where_clause) = mutable_generics.split_for_impl();
+
+        tokens.extend(quote! {
+            impl #impl_generics Mutable<#output> for #struct_ident #ty_generics #where_clause {
+                fn mutate(&self, mut rng: &mut ExprRng) -> BoxedExpr<#output> {
+                    branch_using!(rng, {
+                        wrap_in_generic::<#output>(self, rng),
+                        #( #branches )*
+                    })
+                }
+            }
+        });
+    }
+
+    Ok(tokens)
 }
@@ -0,0 +1,97 @@
+// Minimal stand-ins for the expression traits in artifish's `lang::core`, which
+// the derived impls refer to by name.
+
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+use std::ops::Neg;
+
+pub type ExprRng = u64;
+pub type BoxedExpr<T> = Box<dyn Mutable<T>>;
+
+/// `Debug`, so tests can check what a mutation built.
+pub trait Mutable<T>: Debug {
+    fn mutate(&self, rng: &mut ExprRng) -> BoxedExpr<T>;
+}
+
+pub trait ExprTreeNode {
+    fn borrow_nth_child(&self, n: u64) -> &dyn MutableExprSlot;
+    fn borrow_nth_child_mut(&mut self, n: u64) -> &mut dyn MutableExprSlot;
+    fn num_children(&self) -> u64;
+    fn node_name(&self) -> &'static str;
+    fn child_name(&self, n: u64) -> &'static str;
+    fn signature(&self) -> &'static str;
+}
+
+pub trait MutableExprSlot {
+    fn value(&self) -> i64;
+}
+
+#[derive(Clone, Debug)]
+pub struct ExprSlot<T> {
+    pub value: T,
+}
+
+impl<T: Clone + Into<i64>> MutableExprSlot for ExprSlot<T> {
+    fn value(&self) -> i64 {
+        self.value.clone().into()
+    }
+}
+
+impl<T: Clone + Neg<Output = T>> ExprSlot<T> {
+    /// A mutated slot holds the negated value, so tests can tell which child
+    /// changed.
+    pub fn mutate(&self, _rng: &mut ExprRng) -> ExprSlot<T> {
+        ExprSlot {
+            value: -self.value.clone(),
+        }
+    }
+}
+
+/// What `wrap_in_generic` builds: the wrapped node, printed.
+#[derive(Debug)]
+pub struct Wrapped(pub String);
+
+impl<T> Mutable<T> for Wrapped {
+    fn mutate(&self, _rng: &mut ExprRng) -> BoxedExpr<T> {
+        Box::new(Wrapped(self.0.clone()))
+    }
+}
+
+/// What `generate_minimal_expr` builds.
+#[derive(Debug)]
+pub struct Minimal;
+
+impl<T> Mutable<T> for Minimal {
+    fn mutate(&self, _rng: &mut ExprRng) -> BoxedExpr<T> {
+        Box::new(Minimal)
+    }
+}
+
+pub fn wrap_in_generic<T>(expr: &dyn Mutable<T>, _rng: &mut ExprRng) -> BoxedExpr<T> {
+    Box::new(Wrapped(format!("{:?}", expr)))
+}
+
+pub fn generate_minimal_expr<T>(_rng: &mut ExprRng) -> BoxedExpr<T> {
+    Box::new(Minimal)
+}
+
+/// Picks branch `*rng % number of branches`, so tests can choose one.
+#[macro_export]
+macro_rules! branch_using {
+    ($rng:ident, { $( $branch:expr ),* $(,)? }) => {{
+        let num_branches = [$( stringify!($branch) ),*].len() as u64;
+        let mut remaining = *$rng % num_branches;
+        let mut result = None;
+        $(
+            if result.is_none() {
+                if remaining == 0 {
+                    result = Some($branch);
+                } else {
+                    remaining -= 1;
+                }
+            }
+        )*
+        result.unwrap()
+    }};
+}
@@ -0,0 +1,8 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[derive(ArtifishExpr)]
+#[expr_tree_node(output = "u8", bound = "T: +", mutate)]
+struct BadBoundExpr<T>(T);
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[derive(ArtifishExpr)]
+enum NotAStruct {
+    A,
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[derive(ArtifishExpr)]
+struct BadFieldExpr {
+    #[expr_tree_node = "not_a_child"]
+    value: u8,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[derive(ArtifishExpr)]
+#[expr_tree_node(output = "i64", mutate, regenerate)]
+struct BothExpr;
+
+fn main() {}
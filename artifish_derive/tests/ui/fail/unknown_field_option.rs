@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[derive(ArtifishExpr)]
+struct BadFieldExpr {
+    #[expr_tree_node(not_a_kid)]
+    value: u8,
+}
+
+fn main() {}
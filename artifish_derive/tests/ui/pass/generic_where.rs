@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[path = "../../support/mod.rs"]
+mod support;
+use support::*;
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "T")]
+struct IdentityExpr<T>
+where
+    T: Copy,
+{
+    value: ExprSlot<T>,
+}
+
+fn main() {
+    let expr = IdentityExpr {
+        value: ExprSlot { value: 7i32 },
+    };
+    assert_eq!(expr.borrow_nth_child(0).value(), 7);
+    assert_eq!(expr.signature(), "Identity(value: T) -> T");
+}
@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[path = "../../support/mod.rs"]
+mod support;
+use support::*;
+
+#[derive(Clone, Debug, ArtifishExpr)]
+#[expr_tree_node(
+    output = "i64",
+    bound = "T: Clone + std::fmt::Debug + std::ops::Neg<Output = T> + Into<i64> + 'static",
+    mutate
+)]
+struct SumExpr<T> {
+    left: ExprSlot<T>,
+    right: ExprSlot<T>,
+}
+
+fn main() {
+    let expr = SumExpr {
+        left: ExprSlot { value: 1i32 },
+        right: ExprSlot { value: 2i32 },
+    };
+    let mutate = |mut rng: ExprRng| format!("{:?}", expr.mutate(&mut rng));
+    // Branch 0 wraps the node, 1 and 2 mutate one of the children.
+    assert_eq!(mutate(0), format!("{:?}", Wrapped(format!("{:?}", expr))));
+    assert_eq!(
+        mutate(1),
+        "SumExpr { left: ExprSlot { value: -1 }, right: ExprSlot { value: 2 } }"
+    );
+    assert_eq!(
+        mutate(2),
+        "SumExpr { left: ExprSlot { value: 1 }, right: ExprSlot { value: -2 } }"
+    );
+}
@@ -0,0 +1,29 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[path = "../../support/mod.rs"]
+mod support;
+use support::*;
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(output = "i64")]
+struct AddExpr {
+    left: ExprSlot<i32>,
+    right: ExprSlot<i32>,
+    #[expr_tree_node(not_a_child)]
+    label: &'static str,
+}
+
+fn main() {
+    let expr = AddExpr {
+        left: ExprSlot { value: 1 },
+        right: ExprSlot { value: 2 },
+        label: "sum",
+    };
+    assert_eq!(expr.num_children(), 2);
+    assert_eq!(expr.borrow_nth_child(1).value(), 2);
+    assert_eq!(expr.node_name(), "Add");
+    assert_eq!(expr.child_name(0), "left");
+    assert_eq!(expr.signature(), "Add(left: i32, right: i32) -> i64");
+    assert_eq!(expr.label, "sum");
+}
@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[path = "../../support/mod.rs"]
+mod support;
+use support::*;
+
+#[derive(Clone, Debug, ArtifishExpr)]
+#[expr_tree_node(output = "i64", regenerate)]
+struct ZeroExpr;
+
+#[derive(Clone, Debug, ArtifishExpr)]
+#[expr_tree_node(output = "i64", regenerate = "generate_zero")]
+struct OneExpr;
+
+fn generate_zero(_rng: &mut ExprRng) -> BoxedExpr<i64> {
+    Box::new(ZeroExpr)
+}
+
+fn mutate(expr: &dyn Mutable<i64>, mut rng: ExprRng) -> String {
+    format!("{:?}", expr.mutate(&mut rng))
+}
+
+fn main() {
+    assert_eq!(ZeroExpr.num_children(), 0);
+    // Branch 0 wraps the node, 1 replaces it.
+    assert_eq!(mutate(&ZeroExpr, 0), r#"Wrapped("ZeroExpr")"#);
+    assert_eq!(mutate(&ZeroExpr, 1), "Minimal");
+    assert_eq!(mutate(&OneExpr, 0), r#"Wrapped("OneExpr")"#);
+    assert_eq!(mutate(&OneExpr, 1), "ZeroExpr");
+}
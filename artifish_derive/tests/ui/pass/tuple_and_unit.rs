@@ -0,0 +1,25 @@
+#[macro_use]
+extern crate artifish_derive;
+
+#[path = "../../support/mod.rs"]
+mod support;
+use support::*;
+
+#[derive(Clone, ArtifishExpr)]
+#[expr_tree_node(name = "Pair")]
+struct PairExpr(ExprSlot<i32>, #[expr_tree_node(not_a_child)] u8, ExprSlot<i32>);
+
+#[derive(Clone, ArtifishExpr)]
+struct LeafExpr;
+
+fn main() {
+    let mut pair = PairExpr(ExprSlot { value: 3 }, 0, ExprSlot { value: 4 });
+    assert_eq!(pair.num_children(), 2);
+    assert_eq!(pair.borrow_nth_child_mut(1).value(), 4);
+    assert_eq!(pair.child_name(1), "2");
+    assert_eq!(pair.signature(), "Pair(0: i32, 2: i32)");
+
+    assert_eq!(LeafExpr.num_children(), 0);
+    assert_eq!(LeafExpr.node_name(), "Leaf");
+    assert_eq!(LeafExpr.signature(), "Leaf()");
+}